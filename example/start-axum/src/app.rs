@@ -14,6 +14,10 @@ pub fn App() -> impl IntoView {
     provide_meta_context();
     // Provides Query Client for entire app.
     provide_query_client();
+    // Prefetches Post Two ahead of visiting its page.
+    use_query_client().register_route_prefetch("/multi", |client, _| {
+        client.prefetch_query(|| 2, get_post_unwrapped, SsrPolicy::ServerAndClient);
+    });
 
     view! {
         <Stylesheet id="leptos" href="/pkg/start-axum.css"/>
@@ -86,7 +90,7 @@ fn HomePage() -> impl IntoView {
     };
 
     let prefetch_two = move |_| {
-        use_query_client().prefetch_query(|| 2, get_post_unwrapped, true);
+        use_query_client().prefetch_route("/multi");
     };
 
     view! {
@@ -137,13 +141,10 @@ fn use_post_query(key: impl Fn() -> u32 + 'static) -> QueryResult<Option<String>
     use_query(
         key,
         get_post_unwrapped,
-        QueryOptions {
-            default_value: None,
-            refetch_interval: None,
-            resource_option: ResourceOption::NonBlocking,
-            stale_time: Some(Duration::from_secs(5)),
-            cache_time: Some(Duration::from_secs(60)),
-        },
+        QueryOptions::builder()
+            .stale_time(Duration::from_secs(5))
+            .cache_time(Duration::from_secs(60))
+            .build(),
     )
 }
 
@@ -0,0 +1,126 @@
+use std::time::Duration;
+
+use crate::FetchContext;
+
+/// Freshness directives from an HTTP `Cache-Control` response header.
+///
+/// Lets a fetcher derive the stale time & cache time of a query from the response,
+/// instead of the static values in [`QueryOptions`](crate::QueryOptions).
+///
+/// Example:
+/// ```no_run
+/// use leptos_query::*;
+/// # #[derive(Clone)]
+/// # struct Monkey;
+/// # struct Response;
+/// # impl Response {
+/// #     fn header(&self, name: &str) -> Option<&str> { todo!() }
+/// #     async fn json(self) -> Monkey { todo!() }
+/// # }
+/// # async fn request(id: u32) -> Response { todo!() }
+///
+/// async fn get_monkey(id: u32) -> Monkey {
+///     let response = request(id).await;
+///     if let Some(header) = response.header("Cache-Control") {
+///         CacheControl::parse(header).apply();
+///     }
+///     response.json().await
+/// }
+/// ```
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct CacheControl {
+    /// The `max-age` directive.
+    pub max_age: Option<Duration>,
+    /// The `stale-while-revalidate` directive.
+    pub stale_while_revalidate: Option<Duration>,
+    /// The `no-cache` directive.
+    pub no_cache: bool,
+    /// The `no-store` directive.
+    pub no_store: bool,
+}
+
+impl CacheControl {
+    /// Parses the value of a `Cache-Control` header. Unknown or malformed directives are ignored.
+    pub fn parse(header: &str) -> Self {
+        let mut cache_control = Self::default();
+        for directive in header.split(',') {
+            let (name, value) = match directive.split_once('=') {
+                Some((name, value)) => (name.trim(), Some(value.trim().trim_matches('"'))),
+                None => (directive.trim(), None),
+            };
+            let seconds = || value.and_then(|v| v.parse().ok()).map(Duration::from_secs);
+
+            if name.eq_ignore_ascii_case("max-age") {
+                cache_control.max_age = seconds();
+            } else if name.eq_ignore_ascii_case("stale-while-revalidate") {
+                cache_control.stale_while_revalidate = seconds();
+            } else if name.eq_ignore_ascii_case("no-cache") {
+                cache_control.no_cache = true;
+            } else if name.eq_ignore_ascii_case("no-store") {
+                cache_control.no_store = true;
+            }
+        }
+        cache_control
+    }
+
+    /// The stale time implied by the directives, or [`None`](Option::None) if they don't specify one.
+    ///
+    /// `no-cache` & `no-store` make the data stale immediately. Otherwise it is `max-age`.
+    pub fn stale_time(&self) -> Option<Duration> {
+        if self.no_cache || self.no_store {
+            Some(Duration::ZERO)
+        } else {
+            self.max_age
+        }
+    }
+
+    /// The cache time implied by the directives, or [`None`](Option::None) if they don't specify one.
+    ///
+    /// `no-store` evicts the data as soon as it is unused. Otherwise it is `max-age` plus `stale-while-revalidate`.
+    pub fn cache_time(&self) -> Option<Duration> {
+        if self.no_store {
+            Some(Duration::ZERO)
+        } else {
+            self.max_age
+                .map(|max_age| max_age + self.stale_while_revalidate.unwrap_or_default())
+        }
+    }
+
+    /// Applies the directives to the query currently being fetched. See [`FetchContext::set_stale_time`].
+    ///
+    /// Only the times specified by the directives are changed.
+    /// Returns `false` if called outside of a fetcher.
+    pub fn apply(&self) -> bool {
+        let Some(context) = FetchContext::current() else {
+            return false;
+        };
+        if let Some(cache_time) = self.cache_time() {
+            context.set_cache_time(Some(cache_time));
+        }
+        if let Some(stale_time) = self.stale_time() {
+            context.set_stale_time(Some(stale_time));
+        }
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_freshness_directives() {
+        let cache_control = CacheControl::parse("public, Max-Age=60, stale-while-revalidate=30");
+
+        assert_eq!(Some(Duration::from_secs(60)), cache_control.stale_time());
+        assert_eq!(Some(Duration::from_secs(90)), cache_control.cache_time());
+
+        let cache_control = CacheControl::parse("no-store");
+        assert_eq!(Some(Duration::ZERO), cache_control.stale_time());
+        assert_eq!(Some(Duration::ZERO), cache_control.cache_time());
+
+        let cache_control = CacheControl::parse("private, max-age=abc");
+        assert_eq!(None, cache_control.stale_time());
+        assert_eq!(None, cache_control.cache_time());
+    }
+}
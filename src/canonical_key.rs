@@ -0,0 +1,199 @@
+use std::{
+    hash::{Hash, Hasher},
+    ops::Deref,
+};
+
+/// Maps keys that are semantically equal, but structurally different, to the same form.
+/// Such as unordered filter sets, case-insensitive strings, or strings with surrounding whitespace.
+///
+/// Wrap the key in [`Canonical`] to use it for queries. Two keys with the same canonical form share a cache entry,
+/// so they don't need to be normalized at every call site.
+///
+/// Example:
+/// ```no_run
+/// use leptos::*;
+/// use leptos_query::*;
+/// # #[derive(Clone, serde::Deserialize, serde::Serialize)]
+/// # struct Monkey { name: String }
+/// # async fn search_monkeys(tags: Vec<String>) -> Vec<Monkey> { todo!() }
+///
+/// // The order & case of the tags doesn't matter.
+/// #[derive(Clone)]
+/// struct Tags(Vec<String>);
+///
+/// impl CanonicalKey for Tags {
+///     type Canonical = Vec<String>;
+///
+///     fn canonical(&self) -> Self::Canonical {
+///         let mut tags: Vec<_> = self.0.iter().map(|tag| tag.trim().to_lowercase()).collect();
+///         tags.sort();
+///         tags.dedup();
+///         tags
+///     }
+/// }
+///
+/// #[component]
+/// fn MonkeySearch(tags: Vec<String>) -> impl IntoView {
+///     let query = use_query(
+///         move || Canonical(Tags(tags.clone())),
+///         |Canonical(Tags(tags))| search_monkeys(tags),
+///         QueryOptions::default(),
+///     );
+///     // ...
+/// }
+/// ```
+pub trait CanonicalKey {
+    /// The canonical form of the key.
+    type Canonical: Hash + Eq;
+
+    /// Computes the canonical form of the key.
+    /// Called whenever the key is hashed or compared, so it should be cheap for large keys.
+    fn canonical(&self) -> Self::Canonical;
+}
+
+/// A query key that is hashed & compared by its [canonical form](CanonicalKey::canonical).
+///
+/// The fetcher receives the key as it was given, the first time its cache entry was created.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Canonical<K>(pub K);
+
+impl<K: CanonicalKey> PartialEq for Canonical<K> {
+    fn eq(&self, other: &Self) -> bool {
+        self.0.canonical() == other.0.canonical()
+    }
+}
+
+impl<K: CanonicalKey> Eq for Canonical<K> {}
+
+impl<K: CanonicalKey> Hash for Canonical<K> {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.0.canonical().hash(state)
+    }
+}
+
+impl<K> Deref for Canonical<K> {
+    type Target = K;
+
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
+
+impl<K> From<K> for Canonical<K> {
+    fn from(key: K) -> Self {
+        Canonical(key)
+    }
+}
+
+/// A query key that is hashed & compared by a string derived from it.
+/// Used by [`use_query_keyed_by_string`](crate::use_query_keyed_by_string), for keys that can't implement `Hash + Eq`.
+#[derive(Clone, Debug)]
+pub struct StringKey<K> {
+    string: String,
+    key: K,
+}
+
+impl<K> StringKey<K> {
+    /// Creates a key, cached by the string `key_string` derives from it.
+    pub fn new(key: K, key_string: impl FnOnce(&K) -> String) -> Self {
+        Self {
+            string: key_string(&key),
+            key,
+        }
+    }
+
+    /// The string the key is cached by.
+    pub fn as_str(&self) -> &str {
+        &self.string
+    }
+
+    /// The key itself.
+    pub fn key(&self) -> &K {
+        &self.key
+    }
+
+    /// Consumes this, returning the key itself.
+    pub fn into_key(self) -> K {
+        self.key
+    }
+}
+
+impl<K> PartialEq for StringKey<K> {
+    fn eq(&self, other: &Self) -> bool {
+        self.string == other.string
+    }
+}
+
+impl<K> Eq for StringKey<K> {}
+
+impl<K> Hash for StringKey<K> {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.string.hash(state)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{provide_query_client, use_query_client, QueryOptions};
+    use leptos::*;
+    use std::{cell::RefCell, rc::Rc};
+
+    #[derive(Clone, Debug)]
+    struct Name(&'static str);
+
+    impl CanonicalKey for Name {
+        type Canonical = String;
+
+        fn canonical(&self) -> Self::Canonical {
+            self.0.trim().to_lowercase()
+        }
+    }
+
+    #[test]
+    fn equivalent_keys_share_an_entry() {
+        let _ = create_runtime();
+
+        provide_query_client();
+        let client = use_query_client();
+
+        let names = client.cache::<Canonical<Name>, u32>();
+        names.set(Canonical(Name("George")), |_| Some(1));
+
+        assert_eq!(Some(1), names.get_data(Canonical(Name(" george "))));
+        assert_eq!(None, names.get_data(Canonical(Name("Curious George"))));
+        assert_eq!(1, names.len());
+    }
+
+    #[test]
+    fn string_keys_share_an_entry() {
+        let _ = create_runtime();
+
+        provide_query_client();
+
+        let fetched = Rc::new(RefCell::new(Vec::new()));
+        let position = create_rw_signal(1.0_f64);
+        let query = crate::use_query_keyed_by_string(
+            |position: &f64| format!("{position:.1}"),
+            move || position.get(),
+            {
+                let fetched = fetched.clone();
+                move |position| {
+                    fetched.borrow_mut().push(position);
+                    async move { position * 2.0 }
+                }
+            },
+            QueryOptions::default(),
+        );
+        create_isomorphic_effect(move |_| query.data.track());
+        assert_eq!(Some(2.0), query.data.get_untracked());
+
+        // Same string, so the cached data is kept.
+        position.set(1.04);
+        assert_eq!(Some(2.0), query.data.get_untracked());
+
+        position.set(1.5);
+        assert_eq!(Some(3.0), query.data.get_untracked());
+        assert_eq!(vec![1.0, 1.5], *fetched.borrow());
+    }
+}
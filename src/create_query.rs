@@ -0,0 +1,138 @@
+use leptos::*;
+use std::{future::Future, hash::Hash, pin::Pin, rc::Rc};
+
+use crate::{
+    use_query, use_query_client, QueryOptions, QueryResult, QueryState, RefetchFn, SsrPolicy,
+};
+
+type BoxedFetcher<K, V> = Rc<dyn Fn(K) -> Pin<Box<dyn Future<Output = V>>>>;
+
+/// Creates a reusable query definition, combining a fetcher with its options.
+///
+/// The returned [`QueryScope`] can be stored and shared, then used in any component with [`QueryScope::use_query`],
+/// so the fetcher and options are defined in a single place.
+///
+/// Example
+/// ```no_run
+/// use leptos::*;
+/// use leptos_query::*;
+/// use std::time::Duration;
+/// # #[derive(Clone, serde::Deserialize, serde::Serialize)]
+/// # struct Monkey { name: String }
+/// # async fn get_monkey(id: u32) -> Monkey { todo!() }
+///
+/// fn monkey_query() -> QueryScope<u32, Monkey> {
+///     create_query(get_monkey, QueryOptions::stale_time(Duration::from_secs(5)))
+/// }
+///
+/// #[component]
+/// fn MonkeyName(id: u32) -> impl IntoView {
+///     let query = monkey_query().use_query(move || id);
+///
+///     view! {
+///         <Transition fallback=|| ()>
+///             {move || query.data.get().map(|monkey| monkey.name)}
+///         </Transition>
+///     }
+/// }
+/// ```
+pub fn create_query<K, V, Fu>(
+    fetcher: impl Fn(K) -> Fu + 'static,
+    options: QueryOptions<V>,
+) -> QueryScope<K, V>
+where
+    K: Hash + Eq + Clone + 'static,
+    V: Clone + Serializable + 'static,
+    Fu: Future<Output = V> + 'static,
+{
+    QueryScope {
+        fetcher: Rc::new(move |key| Box::pin(fetcher(key))),
+        options,
+    }
+}
+
+/// A reusable query definition. Created with [`create_query`].
+#[derive(Clone)]
+pub struct QueryScope<K, V> {
+    fetcher: BoxedFetcher<K, V>,
+    options: QueryOptions<V>,
+}
+
+impl<K, V> QueryScope<K, V>
+where
+    K: Hash + Eq + Clone + 'static,
+    V: Clone + Serializable + 'static,
+{
+    /// Creates the query in the current component. See [`use_query`](crate::use_query()).
+    pub fn use_query(&self, key: impl Fn() -> K + 'static) -> QueryResult<V, impl RefetchFn> {
+        use_query(key, self.fetcher(), self.options.clone())
+    }
+
+    /// Creates the query in the current component, with options that replace the scope's options.
+    pub fn use_query_with_options(
+        &self,
+        key: impl Fn() -> K + 'static,
+        options: impl Into<MaybeSignal<QueryOptions<V>>>,
+    ) -> QueryResult<V, impl RefetchFn> {
+        use_query(key, self.fetcher(), options)
+    }
+
+    /// Prefetches the query. See [`QueryClient::prefetch_query`](crate::QueryClient::prefetch_query).
+    pub fn prefetch_query(&self, key: impl Fn() -> K + 'static, ssr: impl Into<SsrPolicy>) {
+        use_query_client().prefetch_query(key, self.fetcher(), ssr)
+    }
+
+    /// Retrieves the current state of the query. See [`QueryClient::get_query_state`](crate::QueryClient::get_query_state).
+    pub fn get_query_state(&self, key: impl Fn() -> K + 'static) -> Signal<Option<QueryState<V>>> {
+        use_query_client().get_query_state(key)
+    }
+
+    /// Invalidates the query. See [`QueryClient::invalidate_query`](crate::QueryClient::invalidate_query).
+    pub fn invalidate_query(&self, key: impl std::borrow::Borrow<K>) -> bool {
+        use_query_client().invalidate_query::<K, V>(key)
+    }
+
+    /// Sets the query's data. See [`QueryClient::set_query_data`](crate::QueryClient::set_query_data).
+    pub fn set_query_data(&self, key: K, updater: impl FnOnce(Option<&V>) -> Option<V> + 'static) {
+        use_query_client().set_query_data(key, updater);
+    }
+
+    fn fetcher(&self) -> impl Fn(K) -> Pin<Box<dyn Future<Output = V>>> + 'static {
+        let fetcher = self.fetcher.clone();
+        move |key| fetcher(key)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::provide_query_client;
+
+    #[test]
+    fn query_scope_shares_cache_with_client() {
+        let _ = create_runtime();
+
+        provide_query_client();
+        let client = use_query_client();
+
+        let scope = create_query(
+            |id: u32| async move { id.to_string() },
+            QueryOptions::default(),
+        );
+        scope.prefetch_query(|| 1, true);
+
+        assert_eq!(
+            Some("1".to_string()),
+            client.iter_entries::<u32, String>()[0].1.data().cloned()
+        );
+
+        scope.set_query_data(1, |_| Some("one".to_string()));
+        assert_eq!(
+            Some("one".to_string()),
+            scope
+                .get_query_state(|| 1)
+                .get_untracked()
+                .and_then(|state| state.data().cloned())
+        );
+    }
+}
@@ -0,0 +1,168 @@
+use leptos::{SignalGetUntracked, SignalWithUntracked};
+use std::{cell::Cell, time::Duration};
+
+use crate::{query::Query, Instant, QueryClient};
+
+// When the data of a query was last read, see `QueryClientBuilder::warn_on_unread_refetches`.
+// Queries that were never read count from their creation.
+pub(crate) struct DataReads {
+    pub(crate) last_read: Cell<Instant>,
+    warned: Cell<bool>,
+}
+
+impl Default for DataReads {
+    fn default() -> Self {
+        Self {
+            last_read: Cell::new(Instant::now()),
+            warned: Cell::new(false),
+        }
+    }
+}
+
+impl DataReads {
+    pub(crate) fn record(&self) {
+        self.last_read.set(Instant::now());
+        self.warned.set(false);
+    }
+}
+
+// Fetches are counted over the query's stale time, but never less than this,
+// so refetch loops are caught for queries that are stale right away.
+const MIN_OVERFETCH_WINDOW: Duration = Duration::from_secs(1);
+
+// Debug builds only: warns when a query is fetched more often than `QueryClientBuilder::warn_on_overfetch` allows.
+// Once it warns, the count starts over, so a refetch loop doesn't flood the logs.
+pub(crate) fn check_overfetch<K, V>(client: &QueryClient, query: &Query<K, V>, started_at: Instant)
+where
+    K: 'static,
+    V: 'static,
+{
+    if !cfg!(debug_assertions) {
+        return;
+    }
+    let Some(max_fetches) = client.max_fetches else {
+        return;
+    };
+    let window = query
+        .stale_time
+        .get_untracked()
+        .unwrap_or_default()
+        .max(MIN_OVERFETCH_WINDOW);
+
+    let fetches = {
+        let mut history = query.fetch_history.borrow_mut();
+        history.retain(|fetched_at| started_at.0.saturating_sub(fetched_at.0) < window);
+        history.push_back(started_at);
+        let fetches = history.len();
+        if fetches > max_fetches {
+            history.clear();
+        }
+        fetches
+    };
+
+    if fetches > max_fetches {
+        client.logger.warn(&format!(
+            "Query ({}, {}) was fetched {fetches} times within {window:?}. Check for effects that refetch or invalidate it in a loop, or a stale time that is too short.",
+            std::any::type_name::<K>(),
+            std::any::type_name::<V>(),
+        ));
+    }
+}
+
+// Debug builds only: warns when an observed query refetches, but none of its observers has read its data for a while.
+// Warns once, until the data is read again.
+pub(crate) fn check_unread_refetch<K, V>(
+    client: &QueryClient,
+    query: &Query<K, V>,
+    started_at: Instant,
+) where
+    K: 'static,
+    V: 'static,
+{
+    if !cfg!(debug_assertions) {
+        return;
+    }
+    let Some(unread_after) = client.unread_refetch_after else {
+        return;
+    };
+    let refetch = query.state.with_untracked(|state| state.data().is_some());
+    if !refetch || query.observers.get() == 0 || query.reads.warned.get() {
+        return;
+    }
+
+    let unread_for = started_at.0.saturating_sub(query.reads.last_read.get().0);
+    if unread_for >= unread_after {
+        query.reads.warned.set(true);
+        client.logger.warn(&format!(
+            "Query ({}, {}) keeps refetching, but its data hasn't been read for {unread_for:?}. Its observers may not need it, or it may need a longer stale time or refetch interval.",
+            std::any::type_name::<K>(),
+            std::any::type_name::<V>(),
+        ));
+    }
+}
+
+#[cfg(test)]
+#[cfg(debug_assertions)]
+mod tests {
+    use crate::{
+        provide_query_client_with, query_logger::RecordingLogger, use_query_client, Instant,
+        QueryClientBuilder,
+    };
+    use leptos::*;
+    use std::time::Duration;
+
+    #[test]
+    fn warns_when_query_is_fetched_too_often() {
+        let _ = create_runtime();
+
+        let logger = RecordingLogger::default();
+        provide_query_client_with(
+            QueryClientBuilder::new()
+                .logger(logger.clone())
+                .warn_on_overfetch(2),
+        );
+        let client = use_query_client();
+
+        client.set_query_data::<u32, u32>(0, |_| Some(0));
+        for _ in 0..2 {
+            client.refetch_query_with::<u32, u32, _>(0, |_| async { 1 });
+        }
+        assert!(logger.warnings().is_empty());
+
+        client.refetch_query_with::<u32, u32, _>(0, |_| async { 1 });
+        client.refetch_query_with::<u32, u32, _>(0, |_| async { 1 });
+        assert_eq!(1, logger.warnings().len());
+        assert!(logger.warnings()[0].contains("fetched 3 times"));
+    }
+
+    #[test]
+    fn warns_when_refetched_data_is_never_read() {
+        let _ = create_runtime();
+
+        let logger = RecordingLogger::default();
+        provide_query_client_with(
+            QueryClientBuilder::new()
+                .logger(logger.clone())
+                .warn_on_unread_refetches(Duration::from_secs(60)),
+        );
+        let client = use_query_client();
+
+        let result = client.fetch_query(|| 0_u32, |_| async { 1_u32 }, true);
+        let (query, _) = client.get_or_create_query::<u32, u32>(0);
+        let unread = || query.reads.last_read.set(Instant::from_millis(0));
+        let refetch = || client.refetch_query_with::<u32, u32, _>(0, |_| async { 2 });
+
+        unread();
+        refetch();
+        refetch();
+        assert_eq!(1, logger.warnings().len());
+
+        // Reading the data resets the warning.
+        assert_eq!(Some(2), result.data.get_untracked());
+        refetch();
+        assert_eq!(1, logger.warnings().len());
+        unread();
+        refetch();
+        assert_eq!(2, logger.warnings().len());
+    }
+}
@@ -0,0 +1,483 @@
+use std::{
+    any::Any,
+    cell::RefCell,
+    future::{poll_fn, Future},
+    pin::{pin, Pin},
+    rc::Rc,
+    task::{Context, Poll},
+    time::Duration,
+};
+
+use futures_core::Stream;
+use leptos::{RwSignal, SignalSet};
+
+use crate::Instant;
+
+/// Arbitrary metadata attached to a query through [`QueryOptions::meta`](crate::QueryOptions::meta).
+///
+/// Can hold any `'static` value, such as a `HashMap<String, String>` or a user defined type.
+#[derive(Clone)]
+pub struct QueryMeta(Rc<dyn Any>);
+
+impl QueryMeta {
+    /// Wraps a value as query metadata.
+    pub fn new<M: 'static>(meta: M) -> Self {
+        Self(Rc::new(meta))
+    }
+
+    /// Returns the metadata if it is of type `M`.
+    pub fn get<M: 'static>(&self) -> Option<&M> {
+        self.0.downcast_ref::<M>()
+    }
+}
+
+impl std::fmt::Debug for QueryMeta {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_tuple("QueryMeta").finish_non_exhaustive()
+    }
+}
+
+thread_local! {
+    static CURRENT_FETCH_CONTEXT: RefCell<Option<FetchContext>> = const { RefCell::new(None) };
+}
+
+/// Information about the query whose fetcher is currently executing.
+///
+/// Retrieve it with [`FetchContext::current`] from within a fetcher, so generic fetchers can vary their behavior per query.
+///
+/// Example:
+/// ```
+/// use leptos_query::*;
+/// use std::collections::HashMap;
+///
+/// async fn get_monkey(id: String) -> String {
+///     let auth_scope = FetchContext::current()
+///         .and_then(|context| context.meta().cloned())
+///         .and_then(|meta| meta.get::<HashMap<String, String>>().cloned())
+///         .and_then(|meta| meta.get("auth_scope").cloned());
+///
+///     todo!()
+/// }
+/// ```
+#[derive(Clone, Debug)]
+pub struct FetchContext {
+    pub(crate) meta: Option<QueryMeta>,
+    pub(crate) freshness: Rc<RefCell<Freshness>>,
+    pub(crate) previous: Previous,
+    pub(crate) partial: Option<Partial>,
+    pub(crate) progress: Option<RwSignal<Option<f32>>>,
+}
+
+// The data of the query being fetched, from before the fetch.
+// The data is only cloned if the fetcher asks for it.
+#[derive(Clone, Default)]
+pub(crate) struct Previous {
+    pub(crate) updated_at: Option<Instant>,
+    #[allow(clippy::type_complexity)]
+    pub(crate) data: Option<Rc<dyn Fn() -> Option<Box<dyn Any>>>>,
+}
+
+impl std::fmt::Debug for Previous {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Previous")
+            .field("updated_at", &self.updated_at)
+            .finish_non_exhaustive()
+    }
+}
+
+// Replaces the data of the query being fetched, while the fetch continues.
+// Returns false if the data isn't of the query's type, or the fetch was cancelled.
+#[derive(Clone)]
+pub(crate) struct Partial(pub(crate) Rc<dyn Fn(Box<dyn Any>) -> bool>);
+
+impl std::fmt::Debug for Partial {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_tuple("Partial").finish_non_exhaustive()
+    }
+}
+
+// Per entry overrides for the query's options, set while fetching.
+#[derive(Clone, Copy, Debug, Default)]
+pub(crate) struct Freshness {
+    pub(crate) stale_time: Option<Option<Duration>>,
+    pub(crate) cache_time: Option<Option<Duration>>,
+}
+
+impl FetchContext {
+    /// Returns the context of the fetcher that is currently executing.
+    ///
+    /// Returns [`None`](Option::None) if called outside of a fetcher.
+    pub fn current() -> Option<FetchContext> {
+        CURRENT_FETCH_CONTEXT.with(|current| current.borrow().clone())
+    }
+
+    /// The metadata of the query being fetched.
+    pub fn meta(&self) -> Option<&QueryMeta> {
+        self.meta.as_ref()
+    }
+
+    /// The data of the query being fetched, from before this fetch. [`None`](Option::None) if it had no data, or it isn't of type `V`.
+    ///
+    /// Lets a fetcher request only the changes since the data it already has, such as a patch or a diff,
+    /// and apply them to a copy of that data instead of transferring all of it again.
+    ///
+    /// Example:
+    /// ```no_run
+    /// use leptos_query::*;
+    /// # #[derive(Clone)]
+    /// # struct Table { version: u64, rows: Vec<String> }
+    /// # struct Patch;
+    /// # impl Table { fn apply(&mut self, patch: Patch) { todo!() } }
+    /// # async fn get_table(id: u32) -> Table { todo!() }
+    /// # async fn get_table_patch(id: u32, since_version: u64) -> Patch { todo!() }
+    ///
+    /// async fn fetch_table(id: u32) -> Table {
+    ///     let previous = FetchContext::current().and_then(|context| context.previous_data::<Table>());
+    ///     match previous {
+    ///         Some(mut table) => {
+    ///             let patch = get_table_patch(id, table.version).await;
+    ///             table.apply(patch);
+    ///             table
+    ///         }
+    ///         None => get_table(id).await,
+    ///     }
+    /// }
+    /// ```
+    pub fn previous_data<V: 'static>(&self) -> Option<V> {
+        let data = self.previous.data.as_ref()?();
+        data?.downcast::<V>().ok().map(|data| *data)
+    }
+
+    /// When the data of the query being fetched was last updated, before this fetch.
+    /// [`None`](Option::None) if it had no data.
+    ///
+    /// Useful for conditional requests, such as `If-Modified-Since`, or requesting the changes since then.
+    pub fn previous_updated_at(&self) -> Option<Instant> {
+        self.previous.updated_at
+    }
+
+    /// Shows partial data for the query being fetched, while the fetch continues.
+    ///
+    /// Each call replaces the query's data, and the query stays [`Fetching`](crate::QueryState::Fetching)
+    /// until the fetcher returns the final data. Useful for fetchers that consume a stream or a paginated source,
+    /// such as search results arriving in chunks.
+    ///
+    /// Returns false if the data isn't of the query's type, or the fetch was cancelled.
+    /// Quiet refetches ignore partial data, and only show the final data.
+    /// Partial data keeps the update time of the data it replaces, or the start time of the fetch if there was none.
+    /// A cancelled fetch keeps the partial data it already showed, marked as stale.
+    /// To show the items of a [`Stream`] as they arrive, use [`stream_partial_data`](Self::stream_partial_data).
+    ///
+    /// Example:
+    /// ```no_run
+    /// use leptos_query::*;
+    /// # struct Chunks;
+    /// # impl Chunks { async fn next(&mut self) -> Option<Vec<String>> { todo!() } }
+    /// # async fn search_chunks(query: String) -> Chunks { todo!() }
+    ///
+    /// async fn search(query: String) -> Vec<String> {
+    ///     let context = FetchContext::current();
+    ///     let mut chunks = search_chunks(query).await;
+    ///     let mut results = Vec::new();
+    ///     while let Some(chunk) = chunks.next().await {
+    ///         results.extend(chunk);
+    ///         if let Some(context) = &context {
+    ///             context.set_partial_data(results.clone());
+    ///         }
+    ///     }
+    ///     results
+    /// }
+    /// ```
+    pub fn set_partial_data<V: 'static>(&self, data: V) -> bool {
+        match &self.partial {
+            Some(partial) => (partial.0)(Box::new(data)),
+            None => false,
+        }
+    }
+
+    /// Shows each item of the stream as partial data for the query being fetched, and returns the last item once the stream ends.
+    /// [`None`](Option::None) if the stream ended without items. See [`set_partial_data`](Self::set_partial_data).
+    ///
+    /// Example:
+    /// ```no_run
+    /// use futures_core::Stream;
+    /// use leptos_query::*;
+    ///
+    /// // Each item holds all the results so far.
+    /// async fn search(results: impl Stream<Item = Vec<String>>) -> Vec<String> {
+    ///     let context = FetchContext::current().expect("called from a fetcher");
+    ///     context.stream_partial_data(results).await.unwrap_or_default()
+    /// }
+    /// ```
+    pub async fn stream_partial_data<V: Clone + 'static>(
+        &self,
+        stream: impl Stream<Item = V>,
+    ) -> Option<V> {
+        let mut stream = pin!(stream);
+        let mut last = None;
+        while let Some(item) = poll_fn(|cx| stream.as_mut().poll_next(cx)).await {
+            self.set_partial_data(item.clone());
+            last = Some(item);
+        }
+        last
+    }
+
+    /// Reports the progress of the fetch, from `0.0` to `1.0`.
+    /// Surfaced as [`QueryResult::progress`](crate::QueryResult::progress), so long downloads can render a real progress bar.
+    ///
+    /// The progress is reset once the fetch completes or is cancelled.
+    ///
+    /// Example:
+    /// ```no_run
+    /// use leptos_query::*;
+    /// # struct Download;
+    /// # impl Download { async fn next_chunk(&mut self) -> Option<Vec<u8>> { todo!() } fn total_bytes(&self) -> usize { todo!() } }
+    /// # async fn start_download(id: u32) -> Download { todo!() }
+    ///
+    /// async fn export(id: u32) -> Vec<u8> {
+    ///     let context = FetchContext::current();
+    ///     let mut download = start_download(id).await;
+    ///     let mut bytes = Vec::new();
+    ///     while let Some(chunk) = download.next_chunk().await {
+    ///         bytes.extend(chunk);
+    ///         if let Some(context) = &context {
+    ///             context.report_progress(bytes.len() as f32 / download.total_bytes() as f32);
+    ///         }
+    ///     }
+    ///     bytes
+    /// }
+    /// ```
+    pub fn report_progress(&self, progress: f32) {
+        if let Some(signal) = self.progress {
+            let progress = if progress.is_nan() {
+                0.0
+            } else {
+                progress.clamp(0.0, 1.0)
+            };
+            signal.try_set(Some(progress));
+        }
+    }
+
+    /// Overrides the stale time of the query being fetched, based on the fetched data.
+    ///
+    /// Applies only to this query's cache entry, and takes precedence over the options of its observers until the next fetch.
+    /// [`None`](Option::None) means the data never becomes stale.
+    pub fn set_stale_time(&self, stale_time: Option<Duration>) {
+        self.freshness.borrow_mut().stale_time = Some(stale_time);
+    }
+
+    /// Overrides the cache time of the query being fetched, based on the fetched data.
+    ///
+    /// Applies only to this query's cache entry, and takes precedence over the options of its observers until the next fetch.
+    /// [`None`](Option::None) means the data is never evicted from the cache.
+    pub fn set_cache_time(&self, cache_time: Option<Duration>) {
+        self.freshness.borrow_mut().cache_time = Some(cache_time);
+    }
+
+    /// Makes this context available to the given future for its entire execution.
+    pub(crate) fn scope<Fu: Future>(self, future: Fu) -> impl Future<Output = Fu::Output> {
+        Scoped {
+            context: self,
+            future: Box::pin(future),
+        }
+    }
+}
+
+struct Scoped<Fu> {
+    context: FetchContext,
+    future: Pin<Box<Fu>>,
+}
+
+impl<Fu: Future> Future for Scoped<Fu> {
+    type Output = Fu::Output;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let context = self.context.clone();
+        let previous = CURRENT_FETCH_CONTEXT.with(|current| current.replace(Some(context)));
+        let result = self.future.as_mut().poll(cx);
+        CURRENT_FETCH_CONTEXT.with(|current| current.replace(previous));
+        result
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{provide_query_client, use_query_client, QueryState};
+    use leptos::*;
+    use std::collections::VecDeque;
+
+    // Yields its items, then ends.
+    struct Chunks(VecDeque<Vec<u32>>);
+
+    impl Stream for Chunks {
+        type Item = Vec<u32>;
+
+        fn poll_next(mut self: Pin<&mut Self>, _: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+            Poll::Ready(self.0.pop_front())
+        }
+    }
+
+    #[test]
+    fn context_is_available_while_fetching() {
+        let context = FetchContext {
+            meta: Some(QueryMeta::new("tracing-label".to_string())),
+            freshness: Default::default(),
+            previous: Default::default(),
+            partial: None,
+            progress: None,
+        };
+
+        leptos::spawn_local(context.scope(async {
+            let label = FetchContext::current()
+                .and_then(|context| context.meta().cloned())
+                .and_then(|meta| meta.get::<String>().cloned());
+
+            assert_eq!(Some("tracing-label".to_string()), label);
+        }));
+
+        assert!(FetchContext::current().is_none());
+    }
+
+    #[test]
+    fn fetcher_sets_partial_data_while_fetching() {
+        let _ = create_runtime();
+
+        provide_query_client();
+        let client = use_query_client();
+
+        client.get_or_create_query::<u32, Vec<u32>>(0);
+        let seen = Rc::new(RefCell::new(Vec::new()));
+        let _watcher = client.watch_query::<u32, Vec<u32>>(&0, {
+            let seen = seen.clone();
+            move |state| {
+                let fetching = matches!(state, QueryState::Fetching(_));
+                seen.borrow_mut().push((fetching, state.data().cloned()))
+            }
+        });
+
+        client.refetch_query_with::<u32, Vec<u32>, _>(0, |_| async {
+            let context = FetchContext::current().unwrap();
+            assert!(!context.set_partial_data("wrong type"));
+            assert!(context.set_partial_data(vec![1_u32]));
+            assert!(context.set_partial_data(vec![1_u32, 2]));
+            vec![1, 2, 3]
+        });
+
+        assert_eq!(
+            vec![
+                (false, None),
+                (true, Some(vec![1])),
+                (true, Some(vec![1, 2])),
+                (false, Some(vec![1, 2, 3])),
+            ],
+            *RefCell::borrow(&seen)
+        );
+    }
+
+    #[test]
+    fn cancelled_partial_data_is_stale() {
+        let _ = create_runtime();
+
+        provide_query_client();
+        let client = use_query_client();
+
+        client.set_query_data::<u32, Vec<u32>>(0, |_| Some(vec![0]));
+        let result = client.fetch_query(|| 0_u32, |_| async { vec![0_u32] }, true);
+        let (query, _) = client.get_or_create_query::<u32, Vec<u32>>(0);
+        let updated_at = query.state.get_untracked().updated_at();
+
+        client.refetch_query_with::<u32, Vec<u32>, _>(0, {
+            let query = query.clone();
+            move |_| async move {
+                let context = FetchContext::current().unwrap();
+                let chunks = Chunks(VecDeque::from([vec![1], vec![1, 2]]));
+                assert_eq!(Some(vec![1, 2]), context.stream_partial_data(chunks).await);
+
+                // Partial data is never fresher than the data it replaces.
+                assert!(matches!(
+                    query.state.get_untracked(),
+                    QueryState::Fetching(_)
+                ));
+                assert_eq!(updated_at, query.state.get_untracked().updated_at());
+                assert!(!result.is_stale.get_untracked());
+
+                assert!(query.cancel());
+                // Yield, so the cancellation is noticed before the fetch completes.
+                let mut yielded = false;
+                poll_fn(|cx| {
+                    if std::mem::replace(&mut yielded, true) {
+                        Poll::Ready(vec![1, 2, 3])
+                    } else {
+                        cx.waker().wake_by_ref();
+                        Poll::Pending
+                    }
+                })
+                .await
+            }
+        });
+
+        // The cancelled fetch keeps its partial data, which is stale.
+        let state = query.state.get_untracked();
+        assert!(matches!(state, QueryState::Loaded(_)));
+        assert_eq!(Some(&vec![1, 2]), state.data());
+        assert!(result.is_stale.get_untracked());
+    }
+
+    #[test]
+    fn fetcher_applies_patch_to_previous_data() {
+        let _ = create_runtime();
+
+        provide_query_client();
+        let client = use_query_client();
+
+        let patch_fetcher = |_: u32| async {
+            let context = FetchContext::current().unwrap();
+            assert_eq!(None, context.previous_data::<String>());
+            let mut rows = context.previous_data::<Vec<u32>>().unwrap_or_default();
+            rows.push(rows.len() as u32);
+            rows
+        };
+
+        client.set_query_data::<u32, Vec<u32>>(0, |_| Some(vec![0, 1]));
+        let updated_at = client
+            .clone()
+            .get_query_state::<u32, Vec<u32>>(|| 0)
+            .get_untracked()
+            .and_then(|state| state.updated_at());
+        client.refetch_query_with::<u32, Vec<u32>, _>(0, move |id| async move {
+            assert_eq!(
+                updated_at,
+                FetchContext::current().unwrap().previous_updated_at()
+            );
+            patch_fetcher(id).await
+        });
+        client.refetch_query_with::<u32, Vec<u32>, _>(0, patch_fetcher);
+
+        assert_eq!(
+            Some(vec![0, 1, 2, 3]),
+            client.iter_entries::<u32, Vec<u32>>()[0].1.data().cloned()
+        );
+    }
+
+    #[test]
+    fn fetcher_reports_progress_until_complete() {
+        let _ = create_runtime();
+
+        provide_query_client();
+        let client = use_query_client();
+
+        let (query, _) = client.get_or_create_query::<u32, u32>(0);
+        let progress = query.progress;
+        client.refetch_query_with::<u32, u32, _>(0, move |_| async move {
+            let context = FetchContext::current().unwrap();
+            context.report_progress(0.5);
+            assert_eq!(Some(0.5), progress.get_untracked());
+            context.report_progress(2.0);
+            assert_eq!(Some(1.0), progress.get_untracked());
+            1
+        });
+
+        assert_eq!(None, progress.get_untracked());
+    }
+}
@@ -0,0 +1,167 @@
+use leptos::*;
+
+/// Keeps track of whether the application is currently focused by the user.
+///
+/// By default the focus status follows the browser's `visibilitychange` & `focus` events (requires the `hydrate` feature).
+/// Desktop webviews or tests can use a [manual](Self::manual) manager and drive the status with [`set_focused`](Self::set_focused).
+///
+/// Example:
+/// ```no_run
+/// use leptos_query::*;
+///
+/// let focus_manager = FocusManager::manual(true);
+///
+/// provide_query_client_with(QueryClientBuilder::new().focus_manager(focus_manager));
+///
+/// // Later, when the native window regains focus.
+/// focus_manager.set_focused(true);
+/// ```
+#[derive(Clone, Copy)]
+pub struct FocusManager {
+    focused: RwSignal<bool>,
+}
+
+impl FocusManager {
+    /// Creates a FocusManager which listens to the browser's `visibilitychange` & `focus` events.
+    ///
+    /// Outside of the browser, the application is always considered focused unless set otherwise.
+    pub fn new() -> Self {
+        let manager = Self::manual(document_visible());
+        manager.listen_to_window();
+        manager
+    }
+
+    /// Creates a FocusManager that is only updated through [`set_focused`](Self::set_focused).
+    pub fn manual(focused: bool) -> Self {
+        Self {
+            focused: create_rw_signal(focused),
+        }
+    }
+
+    /// Updates the focus status. Subscribers are only notified if the status changed.
+    pub fn set_focused(&self, focused: bool) {
+        if self.focused.get_untracked() != focused {
+            self.focused.set(focused);
+        }
+    }
+
+    /// Returns the current focus status, without subscribing to changes.
+    pub fn is_focused(&self) -> bool {
+        self.focused.get_untracked()
+    }
+
+    /// Reactive focus status.
+    pub fn focused(&self) -> Signal<bool> {
+        self.focused.into()
+    }
+
+    /// Invokes the callback every time the focus status changes, for the lifetime of the current scope.
+    pub fn subscribe(&self, callback: impl Fn(bool) + 'static) {
+        let focused = self.focused;
+        create_isomorphic_effect(move |prev: Option<bool>| {
+            let focused = focused.get();
+            if prev.is_some_and(|prev| prev != focused) {
+                callback(focused);
+            }
+            focused
+        });
+    }
+
+    fn listen_to_window(&self) {
+        cfg_if::cfg_if! {
+            if #[cfg(feature = "hydrate")] {
+                let manager = *self;
+                let on_visibility_change = window_event_listener_untyped("visibilitychange", move |_| {
+                    manager.set_focused(document_visible());
+                });
+                let on_focus = window_event_listener(ev::focus, move |_| manager.set_focused(true));
+                on_cleanup(move || {
+                    on_visibility_change.remove();
+                    on_focus.remove();
+                });
+            }
+        }
+    }
+}
+
+impl Default for FocusManager {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+fn document_visible() -> bool {
+    cfg_if::cfg_if! {
+        if #[cfg(all(feature = "hydrate", target_arch = "wasm32"))] {
+            document().visibility_state() != web_sys::VisibilityState::Hidden
+        } else {
+            true
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        provide_query_client_with, use_query_client, QueryClientBuilder, QueryOptions, QueryState,
+    };
+    use std::{cell::RefCell, rc::Rc, time::Duration};
+
+    #[test]
+    fn notifies_subscribers_of_changes_only() {
+        let _ = create_runtime();
+
+        let manager = FocusManager::manual(true);
+        let changes = Rc::new(RefCell::new(Vec::new()));
+        manager.subscribe({
+            let changes = changes.clone();
+            move |focused| changes.borrow_mut().push(focused)
+        });
+
+        manager.set_focused(true);
+        manager.set_focused(false);
+        manager.set_focused(false);
+        assert!(!manager.is_focused());
+        assert!(!manager.focused().get_untracked());
+
+        manager.set_focused(true);
+        assert_eq!(vec![false, true], *changes.borrow());
+    }
+
+    #[test]
+    fn is_focused_outside_the_browser() {
+        let _ = create_runtime();
+
+        assert!(FocusManager::new().is_focused());
+    }
+
+    #[test]
+    fn marks_queries_stale_on_focus_without_fetching() {
+        let _ = create_runtime();
+
+        let focus_manager = FocusManager::manual(true);
+        provide_query_client_with(
+            QueryClientBuilder::new()
+                .focus_manager(focus_manager)
+                .mark_stale_on_focus(),
+        );
+        let client = use_query_client();
+
+        let (short, _) = client.get_or_create_query::<u32, u32>(0);
+        let (long, _) = client.get_or_create_query::<u32, u32>(1);
+        short.register_options(&QueryOptions::stale_time(Duration::ZERO));
+        long.register_options(&QueryOptions::stale_time(Duration::from_secs(60)));
+        client.set_query_data::<u32, u32>(0, |_| Some(1));
+        client.set_query_data::<u32, u32>(1, |_| Some(1));
+
+        focus_manager.set_focused(false);
+        focus_manager.set_focused(true);
+
+        let updated_at = short.state.get_untracked().updated_at();
+        assert!(updated_at.is_some());
+        assert_eq!(updated_at, short.marked_stale.get_untracked());
+        assert!(matches!(short.state.get_untracked(), QueryState::Loaded(_)));
+        assert_eq!(None, long.marked_stale.get_untracked());
+    }
+}
@@ -1,3 +1,5 @@
+#[cfg(test)]
+use std::cell::Cell;
 use std::{
     ops::{Add, Sub},
     time::Duration,
@@ -5,12 +7,29 @@ use std::{
 
 /// Instant that can be used in both wasm and non-wasm environments.
 /// Contains Duration since Unix Epoch (Unix Timestamp).
-#[derive(Copy, Clone, Hash, PartialEq, Eq)]
+///
+/// Unlike [`std::time::Instant`], it is a wall clock timestamp, so it can be persisted or sent between the server and the client.
+/// With the `serde` feature it serializes as whole milliseconds since the Unix Epoch.
+#[derive(Copy, Clone, Hash, PartialEq, Eq, PartialOrd, Ord)]
 pub struct Instant(pub std::time::Duration);
 
 impl Instant {
+    /// Creates an Instant from milliseconds since the Unix Epoch.
+    pub fn from_millis(millis: u64) -> Self {
+        Instant(Duration::from_millis(millis))
+    }
+
+    /// Milliseconds since the Unix Epoch.
+    pub fn as_millis(&self) -> u64 {
+        self.0.as_millis() as u64
+    }
+
     /// Get the current time as a Unix Timestamp.
     pub fn now() -> Self {
+        #[cfg(test)]
+        if let Some(now) = FROZEN_NOW.with(Cell::get) {
+            return Instant(now);
+        }
         cfg_if::cfg_if! {
             if #[cfg(all(feature = "hydrate", target_arch = "wasm32"))] {
                 let millis = js_sys::Date::now();
@@ -26,6 +45,29 @@ impl Instant {
     }
 }
 
+// Tests can freeze the clock, so time-dependent behavior doesn't depend on how fast they run.
+#[cfg(test)]
+thread_local! {
+    static FROZEN_NOW: Cell<Option<Duration>> = const { Cell::new(None) };
+}
+
+#[cfg(test)]
+impl Instant {
+    /// Freezes [`now`](Self::now) at the current time, for the current test thread.
+    pub(crate) fn freeze_now() {
+        let now = Instant::now().0;
+        FROZEN_NOW.with(|frozen| frozen.set(Some(now)));
+    }
+
+    /// Moves the frozen [`now`](Self::now) forward.
+    pub(crate) fn advance_now(by: Duration) {
+        FROZEN_NOW.with(|frozen| {
+            let now = frozen.get().expect("the clock is frozen");
+            frozen.set(Some(now + by));
+        });
+    }
+}
+
 impl Sub<Instant> for Instant {
     type Output = Duration;
 
@@ -54,3 +96,72 @@ impl std::fmt::Debug for Instant {
         f.debug_tuple("Instant").field(&self.0.as_millis()).finish()
     }
 }
+
+impl From<std::time::SystemTime> for Instant {
+    /// Times before the Unix Epoch are clamped to the Epoch.
+    fn from(time: std::time::SystemTime) -> Self {
+        Instant(
+            time.duration_since(std::time::SystemTime::UNIX_EPOCH)
+                .unwrap_or_default(),
+        )
+    }
+}
+
+impl From<Instant> for std::time::SystemTime {
+    fn from(instant: Instant) -> Self {
+        std::time::SystemTime::UNIX_EPOCH + instant.0
+    }
+}
+
+#[cfg(feature = "hydrate")]
+impl From<&js_sys::Date> for Instant {
+    fn from(date: &js_sys::Date) -> Self {
+        Instant(Duration::from_millis(date.get_time().max(0.0) as u64))
+    }
+}
+
+#[cfg(feature = "hydrate")]
+impl From<Instant> for js_sys::Date {
+    fn from(instant: Instant) -> Self {
+        js_sys::Date::new(&(instant.as_millis() as f64).into())
+    }
+}
+
+#[cfg(feature = "serde")]
+impl serde::Serialize for Instant {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_u64(self.as_millis())
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<'de> serde::Deserialize<'de> for Instant {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        <u64 as serde::Deserialize>::deserialize(deserializer).map(Instant::from_millis)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn converts_to_system_time() {
+        let instant = Instant::from_millis(1_700_000_000_123);
+        let system_time: std::time::SystemTime = instant.into();
+
+        assert_eq!(instant, Instant::from(system_time));
+        assert_eq!(1_700_000_000_123, instant.as_millis());
+    }
+
+    #[test]
+    fn frozen_clock_only_moves_when_advanced() {
+        Instant::freeze_now();
+        let frozen = Instant::now();
+        std::thread::sleep(Duration::from_millis(2));
+        assert_eq!(frozen, Instant::now());
+
+        Instant::advance_now(Duration::from_secs(1));
+        assert_eq!(Duration::from_secs(1), Instant::now() - frozen);
+    }
+}
@@ -0,0 +1,75 @@
+use crate::route_prefetch::{get_param, MatchedParams};
+
+/// The parameters of a tag matched by an invalidation.
+/// See [`QueryClient::register_invalidation`](crate::QueryClient::register_invalidation).
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct TagParams {
+    params: Vec<(String, String)>,
+}
+
+impl TagParams {
+    /// Returns the value of the `:name` segment, or of the `*name` wildcard.
+    pub fn get(&self, name: &str) -> Option<&str> {
+        get_param(&self.params, name)
+    }
+}
+
+impl MatchedParams for TagParams {
+    fn from_matched(params: Vec<(String, String)>) -> Self {
+        Self { params }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{provide_query_client, use_query_client, QueryClient, QueryState};
+    use leptos::*;
+
+    #[test]
+    fn invalidates_registered_tags() {
+        let _ = create_runtime();
+
+        provide_query_client();
+        let client = use_query_client();
+
+        client.set_query_data::<u32, u32>(1, |_| Some(10));
+        client.set_query_data::<u32, u32>(2, |_| Some(20));
+        client.set_query_data::<(), u32>((), |_| Some(2));
+
+        client
+            .register_invalidation("post/:id", |client, params| {
+                if let Some(id) = params.get("id").and_then(|id| id.parse::<u32>().ok()) {
+                    client.invalidate_query::<u32, u32>(id);
+                }
+            })
+            .register_invalidation("post/*rest", |client, _| {
+                client.invalidate_query::<(), u32>(());
+            });
+
+        assert_eq!(2, client.invalidate_tag("post/1"));
+        assert_eq!(0, client.invalidate_tag("user/1"));
+
+        let is_invalid =
+            |state: Option<QueryState<u32>>| matches!(state, Some(QueryState::Invalid(_)));
+        assert!(is_invalid(client.cache::<u32, u32>().get(1)));
+        assert!(!is_invalid(client.cache::<u32, u32>().get(2)));
+        assert!(is_invalid(client.cache::<(), u32>().get(())));
+    }
+
+    #[test]
+    fn unregisters_invalidations_with_their_scope() {
+        let _ = create_runtime();
+
+        provide_query_client();
+        let client = use_query_client();
+
+        let ((), disposer) = as_child_of_current_owner(|client: QueryClient| {
+            client.register_invalidation("post/:id", |_, _| ());
+        })(client.clone());
+        assert_eq!(1, client.invalidate_tag("post/1"));
+
+        // Such as a component unmounting.
+        drop(disposer);
+        assert_eq!(0, client.invalidate_tag("post/1"));
+    }
+}
@@ -59,13 +59,10 @@
 //!     leptos_query::use_query(
 //!         id,
 //!         get_monkey,
-//!         QueryOptions {
-//!             default_value: None,
-//!             refetch_interval: None,
-//!             resource_option: ResourceOption::NonBlocking,
-//!             stale_time: Some(Duration::from_secs(5)),
-//!             cache_time: Some(Duration::from_secs(60)),
-//!         },
+//!         QueryOptions::builder()
+//!             .stale_time(Duration::from_secs(5))
+//!             .cache_time(Duration::from_secs(60))
+//!             .build(),
 //!     )
 //! }
 //!
@@ -73,8 +70,16 @@
 //!
 //! Now you can use the query in any component in your app.
 //!
-//! ```rust
-//!
+//! ```
+//! # use leptos::*;
+//! # use leptos_query::*;
+//! # #[derive(Clone, serde::Deserialize, serde::Serialize)]
+//! # struct Monkey {
+//! #     name: String,
+//! # }
+//! # fn use_monkey_query(id: impl Fn() -> String + 'static) -> QueryResult<Monkey, impl RefetchFn> {
+//! #     use_query(id, |_| async { todo!() }, QueryOptions::default())
+//! # }
 //! #[component]
 //! fn MonkeyView(id: String) -> impl IntoView {
 //!     let QueryResult {
@@ -111,7 +116,7 @@
 //!                    view! { <h2>"Loading..."</h2> }
 //!                }>
 //!                {move || {
-//!                    data()
+//!                    data.get()
 //!                        .map(|monkey| {
 //!                            view! { <h2>{monkey.name}</h2> }
 //!                        })
@@ -123,21 +128,49 @@
 //! ```
 //!
 
+mod cache_control;
+mod canonical_key;
+mod create_query;
+mod diagnostics;
+mod fetch_context;
+mod focus_manager;
 mod instant;
+mod invalidation_tags;
+mod online_manager;
 mod query;
+mod query_cache;
 mod query_client;
+mod query_component;
 mod query_executor;
+mod query_hooks;
+mod query_logger;
 mod query_options;
 mod query_result;
 mod query_state;
+mod route_prefetch;
+mod scheduler;
+#[cfg(feature = "test_utils")]
+pub mod test_utils;
 mod use_query;
 mod util;
 
+pub use cache_control::*;
+pub use canonical_key::*;
+pub use create_query::*;
+pub use fetch_context::*;
+pub use focus_manager::*;
 pub use instant::*;
+pub use invalidation_tags::*;
+pub use online_manager::*;
 use query::*;
+pub use query_cache::*;
 pub use query_client::*;
+pub use query_component::*;
 pub use query_executor::*;
+pub use query_hooks::*;
+pub use query_logger::*;
 pub use query_options::*;
 pub use query_result::*;
 pub use query_state::*;
+pub use route_prefetch::*;
 pub use use_query::*;
@@ -0,0 +1,158 @@
+use leptos::*;
+
+/// Keeps track of whether the application currently has network connectivity.
+///
+/// By default the online status follows the browser's `online` & `offline` window events (requires the `hydrate` feature).
+/// Environments that don't emit those events, such as Tauri/webviews or tests, can use a [manual](Self::manual) manager
+/// and drive the status with [`set_online`](Self::set_online).
+///
+/// Example:
+/// ```no_run
+/// use leptos_query::*;
+///
+/// // Online status is controlled by the application.
+/// let online_manager = OnlineManager::manual(true);
+///
+/// provide_query_client_with(QueryClientBuilder::new().online_manager(online_manager));
+///
+/// // Later, when the platform reports a lost connection.
+/// online_manager.set_online(false);
+/// ```
+#[derive(Clone, Copy)]
+pub struct OnlineManager {
+    online: RwSignal<bool>,
+}
+
+impl OnlineManager {
+    /// Creates an OnlineManager which listens to the browser's `online` & `offline` events.
+    ///
+    /// Outside of the browser, the application is always considered online unless set otherwise.
+    pub fn new() -> Self {
+        let manager = Self::manual(navigator_online());
+        manager.listen_to_window();
+        manager
+    }
+
+    /// Creates an OnlineManager that is only updated through [`set_online`](Self::set_online).
+    pub fn manual(online: bool) -> Self {
+        Self {
+            online: create_rw_signal(online),
+        }
+    }
+
+    /// Updates the online status. Subscribers are only notified if the status changed.
+    pub fn set_online(&self, online: bool) {
+        if self.online.get_untracked() != online {
+            self.online.set(online);
+        }
+    }
+
+    /// Returns the current online status, without subscribing to changes.
+    pub fn is_online(&self) -> bool {
+        self.online.get_untracked()
+    }
+
+    /// Reactive online status.
+    pub fn online(&self) -> Signal<bool> {
+        self.online.into()
+    }
+
+    /// Invokes the callback every time the online status changes, for the lifetime of the current scope.
+    pub fn subscribe(&self, callback: impl Fn(bool) + 'static) {
+        let online = self.online;
+        create_effect(move |prev: Option<bool>| {
+            let online = online.get();
+            if prev.is_some_and(|prev| prev != online) {
+                callback(online);
+            }
+            online
+        });
+    }
+
+    fn listen_to_window(&self) {
+        cfg_if::cfg_if! {
+            if #[cfg(feature = "hydrate")] {
+                let manager = *self;
+                let on_online = window_event_listener(ev::online, move |_| manager.set_online(true));
+                let on_offline = window_event_listener(ev::offline, move |_| manager.set_online(false));
+                on_cleanup(move || {
+                    on_online.remove();
+                    on_offline.remove();
+                });
+            }
+        }
+    }
+}
+
+impl Default for OnlineManager {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+fn navigator_online() -> bool {
+    cfg_if::cfg_if! {
+        if #[cfg(all(feature = "hydrate", target_arch = "wasm32"))] {
+            window().navigator().on_line()
+        } else {
+            true
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{provide_query_client_with, use_query_client, QueryClientBuilder, QueryState};
+    use std::{cell::RefCell, rc::Rc};
+
+    #[test]
+    fn can_drive_online_manager_manually() {
+        let _ = create_runtime();
+
+        provide_query_client_with(
+            QueryClientBuilder::new().online_manager(OnlineManager::manual(false)),
+        );
+        let client = use_query_client();
+        let online = client.online_manager().online();
+
+        let changes = Rc::new(RefCell::new(Vec::new()));
+        client.online_manager().subscribe({
+            let changes = changes.clone();
+            move |online| changes.borrow_mut().push(online)
+        });
+
+        assert!(!online.get_untracked());
+
+        client.online_manager().set_online(true);
+        client.online_manager().set_online(true);
+
+        assert!(online.get_untracked());
+        assert_eq!(vec![true], *RefCell::borrow(&changes));
+    }
+
+    #[test]
+    fn fetch_waits_for_network() {
+        let _ = create_runtime();
+
+        provide_query_client_with(
+            QueryClientBuilder::new().online_manager(OnlineManager::manual(false)),
+        );
+        let client = use_query_client();
+
+        let state = client.clone().get_query_state::<u32, String>(|| 0);
+
+        client
+            .clone()
+            .prefetch_query(|| 0, |num: u32| async move { num.to_string() }, true);
+
+        assert!(matches!(state.get_untracked(), Some(QueryState::Created)));
+
+        client.online_manager().set_online(true);
+
+        assert_eq!(
+            Some("0".to_string()),
+            state.get_untracked().and_then(|q| q.data().cloned())
+        );
+    }
+}
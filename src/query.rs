@@ -1,7 +1,19 @@
 use leptos::*;
-use std::{cell::Cell, rc::Rc, time::Duration};
+use std::{
+    cell::{Cell, RefCell},
+    collections::{BTreeMap, VecDeque},
+    rc::Rc,
+    task::Waker,
+    time::Duration,
+};
 
-use crate::{ensure_valid_stale_time, QueryOptions, QueryState};
+use crate::{
+    diagnostics::DataReads, ensure_valid_stale_time, fetch_context::Freshness,
+    EffectiveQueryOptions, Instant, QueryData, QueryMeta, QueryOptions, QueryState, ResourceOption,
+};
+
+// Fetches within this long of the previous one are considered duplicates of it.
+const DUPLICATE_FETCH_WINDOW: Duration = Duration::from_millis(10);
 
 #[derive(Clone)]
 pub(crate) struct Query<K, V>
@@ -13,10 +25,72 @@ where
     // State.
     pub(crate) observers: Rc<Cell<usize>>,
     pub(crate) state: RwSignal<QueryState<V>>,
+    // Incremented on every write to the state, see `QueryClient::try_set_query_data`.
+    pub(crate) version: Rc<Cell<u64>>,
+    // Fetch is waiting for the network to come back.
+    pub(crate) paused: RwSignal<bool>,
+    // Invalidated while fetching. The fetched data is invalidated once, however many times this happened.
+    pub(crate) invalidated_while_fetching: Rc<Cell<bool>>,
+    // Fetching without toggling the state to Fetching, see `QueryResult::refetch_quietly`.
+    pub(crate) fetching_quietly: RwSignal<bool>,
+    // The next fetch doesn't toggle the state to Fetching.
+    pub(crate) quiet_next_fetch: Rc<Cell<bool>>,
+    // The next fetch was requested explicitly, so it's never skipped as a duplicate.
+    pub(crate) refetch_requested: Rc<Cell<bool>>,
+    // Incremented when the current fetch is cancelled, so its result is discarded.
+    pub(crate) fetch_generation: Rc<Cell<u64>>,
+    // Wakes the current fetch, so it notices it was cancelled.
+    pub(crate) fetch_waker: Rc<RefCell<Option<Waker>>>,
+    // The data was set by the current fetch before it completed. See `FetchContext::set_partial_data`.
+    pub(crate) partial: Rc<Cell<bool>>,
+    // Progress of the current fetch, reported by the fetcher. See `FetchContext::report_progress`.
+    pub(crate) progress: RwSignal<Option<f32>>,
+    // Update time of the data that was marked stale when the app regained focus. Newer data isn't stale.
+    pub(crate) marked_stale: RwSignal<Option<Instant>>,
+    // Start times of recent fetches, see `QueryClientBuilder::warn_on_overfetch`.
+    pub(crate) fetch_history: Rc<RefCell<VecDeque<Instant>>>,
+    // When the data was last read, see `QueryClientBuilder::warn_on_unread_refetches`.
+    pub(crate) reads: Rc<DataReads>,
+    // Update time of the last fetched data, to tell it apart from data that was set manually.
+    pub(crate) fetched_at: Rc<Cell<Option<Instant>>>,
     // Config.
     pub(crate) stale_time: RwSignal<Option<Duration>>,
     pub(crate) cache_time: RwSignal<Option<Duration>>,
     pub(crate) refetch_interval: RwSignal<Option<Duration>>,
+    pub(crate) meta: RwSignal<Option<QueryMeta>>,
+    // Options of each observer, merged into the config above.
+    pub(crate) observer_options: Rc<RefCell<ObserverOptionsRegistry<V>>>,
+    // Overrides of the merged options for this entry, until the next fetch.
+    pub(crate) freshness: Rc<Cell<Freshness>>,
+    // Removed from the cache while observed, so it's disposed once its last observer is gone.
+    pub(crate) evicted: Rc<Cell<bool>>,
+    pub(crate) disposed: Rc<Cell<bool>>,
+}
+
+pub(crate) struct ObserverOptionsRegistry<V> {
+    next_id: u64,
+    options: BTreeMap<u64, ObserverOptions<V>>,
+}
+
+impl<V> Default for ObserverOptionsRegistry<V> {
+    fn default() -> Self {
+        Self {
+            next_id: 0,
+            options: BTreeMap::new(),
+        }
+    }
+}
+
+// A success or settled callback of an observer.
+pub(crate) type FetchCallback<V> = Rc<dyn Fn(&V)>;
+
+struct ObserverOptions<V> {
+    stale_time: Option<Duration>,
+    cache_time: Option<Duration>,
+    refetch_interval: Option<Duration>,
+    blocking: bool,
+    on_success: Option<FetchCallback<V>>,
+    on_settled: Option<FetchCallback<V>>,
 }
 
 impl<K: PartialEq, V> PartialEq for Query<K, V> {
@@ -36,15 +110,37 @@ where
         let stale_time = create_rw_signal(None);
         let cache_time = create_rw_signal(None);
         let refetch_interval = create_rw_signal(None);
+        let meta = create_rw_signal(None);
         let state = create_rw_signal(QueryState::Created);
+        let paused = create_rw_signal(false);
+        let marked_stale = create_rw_signal(None);
 
         Query {
             key,
             observers: Rc::new(Cell::new(0)),
             state,
+            version: Rc::default(),
+            paused,
+            invalidated_while_fetching: Rc::new(Cell::new(false)),
+            fetching_quietly: create_rw_signal(false),
+            quiet_next_fetch: Rc::new(Cell::new(false)),
+            refetch_requested: Rc::new(Cell::new(false)),
+            fetch_generation: Rc::new(Cell::new(0)),
+            fetch_waker: Rc::default(),
+            partial: Rc::default(),
+            progress: create_rw_signal(None),
+            marked_stale,
+            fetch_history: Rc::default(),
+            reads: Rc::default(),
+            fetched_at: Rc::default(),
             stale_time,
             cache_time,
             refetch_interval,
+            meta,
+            observer_options: Rc::default(),
+            freshness: Rc::default(),
+            evicted: Rc::default(),
+            disposed: Rc::default(),
         }
     }
 }
@@ -54,79 +150,362 @@ where
     K: Clone + 'static,
     V: Clone + 'static,
 {
+    /// Writes the state. Every write counts as a new version of the data, even if only its status changed.
+    pub(crate) fn set_state(&self, state: QueryState<V>) {
+        self.version.set(self.version.get() + 1);
+        self.state.set(state);
+    }
+
+    /// If a fetch is in flight, including a quiet one that keeps the state as is.
+    pub(crate) fn is_fetching(&self) -> bool {
+        self.fetching_quietly.get_untracked()
+            || self.state.with_untracked(|state| {
+                matches!(state, QueryState::Loading | QueryState::Fetching(_))
+            })
+    }
+
     /// Marks the resource as invalid, which will cause it to be refetched on next read.
+    /// If a fetch is in progress, its result is marked as invalid once it completes, since it may predate the invalidation.
     pub(crate) fn mark_invalid(&self) -> bool {
-        if let QueryState::Loaded(data) = self.state.get_untracked() {
-            self.state.set(QueryState::Invalid(data));
-            true
-        } else {
-            false
+        if self.is_fetching() {
+            // Not invalid yet, so this still returns false.
+            self.invalidated_while_fetching.set(true);
+            return false;
+        }
+        match self.state.get_untracked() {
+            QueryState::Loaded(data) => {
+                self.set_state(QueryState::Invalid(data));
+                true
+            }
+            _ => false,
         }
     }
 
-    pub(crate) fn overwrite_options(&self, options: QueryOptions<V>) {
-        let stale_time = ensure_valid_stale_time(&options.stale_time, &options.cache_time);
+    /// Cancels the current fetch, discarding its result. The query goes back to the state it had before fetching.
+    /// Also cancels a fetch that is waiting for the network.
+    pub(crate) fn cancel(&self) -> bool {
+        let paused = self.paused.get_untracked();
+        if paused {
+            self.paused.set(false);
+        }
+        let restored = match self.state.get_untracked() {
+            QueryState::Loading => Some(QueryState::Created),
+            QueryState::Fetching(data) => Some(QueryState::Loaded(data)),
+            _ if self.fetching_quietly.get_untracked() => None,
+            _ => return paused,
+        };
+        self.fetch_generation.set(self.fetch_generation.get() + 1);
+        self.invalidated_while_fetching.set(false);
+        // Partial data is incomplete, so it's stale once the fetch that was completing it is cancelled.
+        if let (true, Some(QueryState::Loaded(data))) = (self.partial.replace(false), &restored) {
+            self.marked_stale.set(Some(data.updated_at));
+        }
+        match restored {
+            Some(restored) => self.set_state(restored),
+            None => self.fetching_quietly.set(false),
+        }
+        if self.progress.get_untracked().is_some() {
+            self.progress.set(None);
+        }
+        if let Some(waker) = self.fetch_waker.take() {
+            waker.wake();
+        }
+        true
+    }
 
-        self.stale_time.set(stale_time);
-        self.cache_time.set(options.cache_time);
-        self.refetch_interval.set(options.refetch_interval);
+    /// If the data was fetched moments ago, so fetching it again would be a duplicate of that fetch.
+    /// Happens when effects run in quick succession, such as components mounting twice or hydrating.
+    pub(crate) fn fetched_moments_ago(&self, data: &QueryData<V>) -> bool {
+        self.fetched_at.get() == Some(data.updated_at)
+            && Instant::now().0.saturating_sub(data.updated_at.0) < DUPLICATE_FETCH_WINDOW
     }
 
-    // Enables having different stale times & refetch intervals for the same query.
-    // The lowest stale time & refetch interval will be used.
-    // When the scope is dropped, the stale time & refetch interval will be reset to the previous value (if they existed).
-    // Cache time behaves differently. It will only use the minimum cache time found.
-    pub(crate) fn update_options(&self, options: QueryOptions<V>) {
-        // Use the minimum cache time.
-        match (self.cache_time.get_untracked(), options.cache_time) {
-            (Some(current), Some(new)) if new < current => self.cache_time.set(Some(new)),
-            (None, Some(new)) => self.cache_time.set(Some(new)),
-            _ => (),
+    /// Marks the data as stale if the app was hidden for at least the stale time, without refetching it.
+    pub(crate) fn mark_stale_after_hidden(&self, hidden: Duration) -> bool {
+        let updated_at = self.state.with_untracked(|state| state.updated_at());
+        match (updated_at, self.stale_time.get_untracked()) {
+            (Some(updated_at), Some(stale_time)) if hidden >= stale_time => {
+                if self.marked_stale.get_untracked() != Some(updated_at) {
+                    self.marked_stale.set(Some(updated_at));
+                }
+                true
+            }
+            _ => false,
         }
+    }
 
-        let curr_stale = self.stale_time.get_untracked();
-        let curr_refetch_interval = self.refetch_interval.get_untracked();
-
-        let (prev_stale, new_stale) = match (curr_stale, options.stale_time) {
-            (Some(current), Some(new)) if new < current => (Some(current), Some(new)),
-            (None, Some(new)) => (None, Some(new)),
-            _ => (None, None),
-        };
+    // Registers an observer's options, and returns the id to unregister them with.
+    pub(crate) fn register_options(&self, options: &QueryOptions<V>) -> u64 {
+        if options.meta.is_some() {
+            self.meta.set(options.meta.clone());
+        }
 
-        let (prev_refetch, new_refetch) = match (curr_refetch_interval, options.refetch_interval) {
-            (Some(current), Some(new)) if new < current => (Some(current), Some(new)),
-            (None, Some(new)) => (None, Some(new)),
-            _ => (None, None),
+        let id = {
+            let mut observer_options = self.observer_options.borrow_mut();
+            let id = observer_options.next_id;
+            observer_options.next_id += 1;
+            observer_options.options.insert(
+                id,
+                ObserverOptions {
+                    stale_time: options.stale_time,
+                    cache_time: options.cache_time,
+                    refetch_interval: options.refetch_interval,
+                    blocking: matches!(options.resource_option, ResourceOption::Blocking),
+                    on_success: options.on_success.clone(),
+                    on_settled: options.on_settled.clone(),
+                },
+            );
+            id
         };
+        self.merge_options();
+        id
+    }
 
-        if let Some(new_stale) = new_stale {
-            self.stale_time.set(Some(new_stale));
+    pub(crate) fn unregister_options(&self, id: u64) {
+        // Observers of a disposed query clean up after it.
+        if self.disposed.get() {
+            return;
         }
-
-        if let Some(new_refetch) = new_refetch {
-            self.refetch_interval.set(Some(new_refetch));
+        let removed = self.observer_options.borrow_mut().options.remove(&id);
+        if removed.is_some() {
+            self.merge_options();
         }
+    }
 
-        // Reset stale time and refetch interval to previous values when scope is dropped.
-        let stale_time = self.stale_time;
-        let refetch_interval = self.refetch_interval;
-        on_cleanup(move || {
-            if let Some(prev_stale) = prev_stale {
-                stale_time.set(Some(prev_stale));
+    // The success & settled callbacks of the current observers. Observers sharing a callback, such as the options of a `QueryScope`, call it once.
+    pub(crate) fn fetch_callbacks(&self) -> (Vec<FetchCallback<V>>, Vec<FetchCallback<V>>) {
+        fn push<V>(callbacks: &mut Vec<FetchCallback<V>>, callback: &Option<FetchCallback<V>>) {
+            if let Some(callback) = callback {
+                if !callbacks.iter().any(|c| Rc::ptr_eq(c, callback)) {
+                    callbacks.push(callback.clone());
+                }
             }
+        }
+        let (mut on_success, mut on_settled) = (Vec::new(), Vec::new());
+        for options in self.observer_options.borrow().options.values() {
+            push(&mut on_success, &options.on_success);
+            push(&mut on_settled, &options.on_settled);
+        }
+        (on_success, on_settled)
+    }
 
-            if let Some(prev_refetch) = prev_refetch {
-                refetch_interval.set(Some(prev_refetch));
-            }
-        })
+    // Overrides the stale time & cache time of this entry, until the next fetch.
+    // Observers mounting in the meantime don't replace them.
+    pub(crate) fn set_freshness(&self, freshness: Freshness) {
+        self.freshness.set(freshness);
+        self.merge_options();
+    }
+
+    // Enables having different stale times, cache times & refetch intervals for the same query.
+    // The minimum stale time & refetch interval across all observers is used, and the maximum cache time,
+    // so the data is kept as long as the longest-lived observer wants it. An observer without a value doesn't constrain them.
+    // Once the last observer is gone, the query keeps its latest stale & cache times, but stops refetching on an interval.
+    // The entry's freshness overrides take precedence over the observers' options.
+    fn merge_options(&self) {
+        let merged = {
+            let observer_options = self.observer_options.borrow();
+            let options = observer_options.options.values();
+            (!observer_options.options.is_empty()).then(|| {
+                (
+                    options.clone().filter_map(|o| o.stale_time).min(),
+                    options.clone().filter_map(|o| o.cache_time).max(),
+                    options.filter_map(|o| o.refetch_interval).min(),
+                )
+            })
+        };
+        let (stale_time, cache_time, refetch_interval) = merged.unwrap_or_else(|| {
+            (
+                self.stale_time.get_untracked(),
+                self.cache_time.get_untracked(),
+                None,
+            )
+        });
+        let freshness = self.freshness.get();
+        let stale_time = freshness.stale_time.unwrap_or(stale_time);
+        let cache_time = freshness.cache_time.unwrap_or(cache_time);
+        let stale_time = ensure_valid_stale_time(&stale_time, &cache_time);
+
+        if self.stale_time.get_untracked() != stale_time {
+            self.stale_time.set(stale_time);
+        }
+        if self.cache_time.get_untracked() != cache_time {
+            self.cache_time.set(cache_time);
+        }
+        if self.refetch_interval.get_untracked() != refetch_interval {
+            self.refetch_interval.set(refetch_interval);
+        }
+    }
+
+    // Any blocking observer makes the query blocking.
+    pub(crate) fn effective_options(&self) -> EffectiveQueryOptions {
+        let observer_options = self.observer_options.borrow();
+        EffectiveQueryOptions {
+            stale_time: self.stale_time.get_untracked(),
+            cache_time: self.cache_time.get_untracked(),
+            refetch_interval: self.refetch_interval.get_untracked(),
+            blocking: observer_options.options.values().any(|o| o.blocking),
+            observers: observer_options.options.len(),
+        }
     }
 }
 
 impl<K, V> Query<K, V> {
+    // Releases a query that was removed from the cache.
+    // It's disposed right away if unobserved, otherwise once its last observer is gone.
+    pub(crate) fn release(&self) {
+        if self.observers.get() == 0 {
+            self.dispose();
+        } else {
+            self.evicted.set(true);
+        }
+    }
+
+    pub(crate) fn remove_observer(&self) {
+        self.observers.set(self.observers.get() - 1);
+        if self.observers.get() == 0 && self.evicted.get() {
+            self.dispose();
+        }
+    }
+
+    // Disposes the query's signals. A fetch in progress is cancelled, so it doesn't touch them once it resumes.
     pub(crate) fn dispose(&self) {
+        if self.disposed.replace(true) {
+            return;
+        }
+        self.fetch_generation.set(self.fetch_generation.get() + 1);
+        if let Some(waker) = self.fetch_waker.take() {
+            waker.wake();
+        }
         self.state.dispose();
+        self.paused.dispose();
+        self.fetching_quietly.dispose();
+        self.progress.dispose();
+        self.marked_stale.dispose();
         self.stale_time.dispose();
         self.refetch_interval.dispose();
         self.cache_time.dispose();
+        self.meta.dispose();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{provide_query_client, use_query_client};
+
+    #[test]
+    fn merges_options_of_all_observers() {
+        let _ = create_runtime();
+
+        provide_query_client();
+        let client = use_query_client();
+
+        let (query, _) = client.get_or_create_query::<u32, u32>(0);
+        let slow = query.register_options(&QueryOptions {
+            stale_time: Some(Duration::from_secs(60)),
+            refetch_interval: None,
+            ..QueryOptions::default()
+        });
+        let fast = query.register_options(&QueryOptions {
+            stale_time: Some(Duration::from_secs(10)),
+            refetch_interval: Some(Duration::from_secs(30)),
+            ..QueryOptions::default()
+        });
+
+        let options = || client.get_query_options::<u32, u32>(0).unwrap();
+        assert_eq!(Some(Duration::from_secs(10)), options().stale_time);
+        assert_eq!(Some(Duration::from_secs(30)), options().refetch_interval);
+        assert_eq!(2, options().observers);
+
+        query.unregister_options(fast);
+        assert_eq!(Some(Duration::from_secs(60)), options().stale_time);
+        assert_eq!(None, options().refetch_interval);
+
+        // Keeps the latest options without observers.
+        query.unregister_options(slow);
+        assert_eq!(Some(Duration::from_secs(60)), options().stale_time);
+        assert_eq!(0, options().observers);
+    }
+
+    #[test]
+    fn keeps_the_longest_cache_time_of_all_observers() {
+        let _ = create_runtime();
+
+        provide_query_client();
+        let client = use_query_client();
+
+        let (query, _) = client.get_or_create_query::<u32, u32>(0);
+        let short = query.register_options(&QueryOptions {
+            stale_time: Some(Duration::from_secs(10)),
+            cache_time: Some(Duration::from_secs(60)),
+            ..QueryOptions::default()
+        });
+        let long = query.register_options(&QueryOptions {
+            stale_time: Some(Duration::from_secs(30)),
+            cache_time: Some(Duration::from_secs(600)),
+            ..QueryOptions::default()
+        });
+
+        let options = || client.get_query_options::<u32, u32>(0).unwrap();
+        assert_eq!(Some(Duration::from_secs(10)), options().stale_time);
+        assert_eq!(Some(Duration::from_secs(600)), options().cache_time);
+
+        query.unregister_options(long);
+        assert_eq!(Some(Duration::from_secs(60)), options().cache_time);
+
+        query.unregister_options(short);
+        assert_eq!(Some(Duration::from_secs(60)), options().cache_time);
+    }
+
+    #[test]
+    fn any_blocking_observer_makes_the_query_blocking() {
+        let _ = create_runtime();
+
+        provide_query_client();
+        let client = use_query_client();
+
+        let (query, _) = client.get_or_create_query::<u32, u32>(0);
+        let blocking = || client.get_query_options::<u32, u32>(0).unwrap().blocking;
+
+        let non_blocking = query.register_options(&QueryOptions::default());
+        assert!(!blocking());
+
+        let blocks = query.register_options(&QueryOptions {
+            resource_option: ResourceOption::Blocking,
+            ..QueryOptions::default()
+        });
+        let also_non_blocking = query.register_options(&QueryOptions::default());
+        // Whatever order the observers registered in.
+        assert!(blocking());
+
+        query.unregister_options(non_blocking);
+        assert!(blocking());
+
+        query.unregister_options(blocks);
+        assert!(!blocking());
+
+        query.unregister_options(also_non_blocking);
+        assert!(!blocking());
+    }
+
+    #[test]
+    fn refetch_interval_only_applies_while_observed() {
+        let _ = create_runtime();
+
+        provide_query_client();
+        let client = use_query_client();
+
+        let (query, _) = client.get_or_create_query::<u32, u32>(0);
+        let options = || client.get_query_options::<u32, u32>(0).unwrap();
+
+        let id = query.register_options(&QueryOptions::refetch_interval(Duration::from_secs(5)));
+        assert_eq!(Some(Duration::from_secs(5)), options().refetch_interval);
+
+        query.unregister_options(id);
+        assert_eq!(None, options().refetch_interval);
+        assert_eq!(Some(Duration::ZERO), options().stale_time);
+
+        query.register_options(&QueryOptions::refetch_interval(Duration::from_secs(5)));
+        assert_eq!(Some(Duration::from_secs(5)), options().refetch_interval);
     }
 }
@@ -0,0 +1,182 @@
+use leptos::{SignalGetUntracked, SignalWithUntracked};
+use std::{borrow::Borrow, cell::RefCell, hash::Hash};
+
+use crate::{query::Query, query_client::Queries, util::KeyMap, QueryClient, QueryState};
+
+/// The queries of a [`QueryClient`] with key type `K` and value type `V`. Created with [`QueryClient::cache`].
+///
+/// The key & value types are fixed once, so a sequence of operations on the same queries can't mix them up,
+/// such as invalidating a key with the wrong value type, which silently does nothing.
+///
+/// Example:
+/// ```no_run
+/// use leptos::*;
+/// use leptos_query::*;
+/// # #[derive(Clone)]
+/// # struct Monkey { name: String }
+///
+/// let monkeys = use_query_client().cache::<u32, Monkey>();
+///
+/// monkeys.set(1, |_| Some(Monkey { name: "George".to_string() }));
+/// let george = monkeys.get_data(1);
+/// for id in monkeys.keys() {
+///     monkeys.invalidate(id);
+/// }
+/// ```
+pub struct QueryCache<K: 'static, V: 'static> {
+    client: QueryClient,
+    // The typed queries, and the cache generation they belong to. Looked up again once the client replaces its cache,
+    // such as when switching partitions.
+    queries: RefCell<(u64, Queries<K, V>)>,
+}
+
+impl<K, V> Clone for QueryCache<K, V> {
+    fn clone(&self) -> Self {
+        Self {
+            client: self.client.clone(),
+            queries: self.queries.clone(),
+        }
+    }
+}
+
+impl<K, V> QueryCache<K, V>
+where
+    K: Hash + Eq + Clone + 'static,
+    V: Clone + 'static,
+{
+    pub(crate) fn new(client: QueryClient) -> Self {
+        let generation = client.cache_generation.get();
+        let queries = client.typed_queries();
+        Self {
+            client,
+            queries: RefCell::new((generation, queries)),
+        }
+    }
+
+    /// The current state of a query, without tracking it. [`None`](Option::None) if it doesn't exist.
+    /// To track it, use [`QueryClient::get_query_state`].
+    pub fn get(&self, key: impl Borrow<K>) -> Option<QueryState<V>> {
+        self.with_queries(|queries| {
+            queries
+                .get(key.borrow())
+                .map(|query| query.state.get_untracked())
+        })
+    }
+
+    /// The current data of a query, without tracking it. [`None`](Option::None) if it doesn't exist or has no data.
+    pub fn get_data(&self, key: impl Borrow<K>) -> Option<V> {
+        self.with_queries(|queries| {
+            queries
+                .get(key.borrow())
+                .and_then(|query| query.state.with_untracked(|state| state.data().cloned()))
+        })
+    }
+
+    /// Sets a query's data. See [`QueryClient::set_query_data`].
+    pub fn set(&self, key: K, updater: impl FnOnce(Option<&V>) -> Option<V> + 'static) -> &Self {
+        self.client.set_query_data(key, updater);
+        self
+    }
+
+    /// Invalidates a query. See [`QueryClient::invalidate_query`].
+    pub fn invalidate(&self, key: impl Borrow<K>) -> bool {
+        self.client.invalidate_query::<K, V>(key)
+    }
+
+    /// Invalidates all of these queries. See [`QueryClient::invalidate_query_type`].
+    pub fn invalidate_all(&self) -> &Self {
+        self.client.invalidate_query_type::<K, V>();
+        self
+    }
+
+    /// The keys of all of these queries.
+    pub fn keys(&self) -> Vec<K> {
+        self.with_queries(|queries| queries.keys().cloned().collect())
+    }
+
+    /// The keys & states of all of these queries. See [`QueryClient::iter_entries`].
+    pub fn entries(&self) -> Vec<(K, QueryState<V>)> {
+        self.with_queries(|queries| {
+            queries
+                .iter()
+                .map(|(key, query)| (key.clone(), query.state.get_untracked()))
+                .collect()
+        })
+    }
+
+    /// The number of these queries.
+    pub fn len(&self) -> usize {
+        self.with_queries(|queries| queries.len())
+    }
+
+    /// If there are none of these queries.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    fn with_queries<R>(&self, func: impl FnOnce(&KeyMap<K, Query<K, V>>) -> R) -> R {
+        let mut queries = self.queries.borrow_mut();
+        let (generation, typed) = &mut *queries;
+        if self.client.cache_generation.get() != *generation {
+            *generation = self.client.cache_generation.get();
+            *typed = self.client.typed_queries();
+        }
+        let typed = RefCell::borrow(typed);
+        func(&typed)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{provide_query_client, use_query_client, QueryState};
+    use leptos::*;
+
+    #[test]
+    fn typed_cache_handle() {
+        let _ = create_runtime();
+
+        provide_query_client();
+        let client = use_query_client();
+
+        let cache = client.cache::<u32, String>();
+        assert!(cache.is_empty());
+
+        cache
+            .set(0, |_| Some("a".to_string()))
+            .set(1, |_| Some("b".to_string()));
+        assert_eq!(2, cache.len());
+        assert_eq!(Some("b".to_string()), cache.get_data(1));
+        assert_eq!(None, cache.get_data(2));
+
+        let mut keys = cache.keys();
+        keys.sort();
+        assert_eq!(vec![0, 1], keys);
+
+        assert!(cache.invalidate(0));
+        assert!(matches!(cache.get(0), Some(QueryState::Invalid(_))));
+        cache.invalidate_all();
+        assert!(matches!(cache.get(1), Some(QueryState::Invalid(_))));
+
+        // Other value types are separate.
+        assert!(client.cache::<u32, u32>().is_empty());
+
+        // The handle follows the client's partition.
+        client.set_partition("other");
+        assert!(cache.is_empty());
+        cache.set(2, |_| Some("c".to_string()));
+        assert_eq!(vec![2], cache.keys());
+        client.clear_partition();
+        assert_eq!(2, cache.len());
+
+        // A partition that is removed and created again starts empty.
+        client.set_partition("other");
+        assert_eq!(vec![2], cache.keys());
+        client.clear_partition();
+        assert!(client.remove_partition("other"));
+        client.set_partition("other");
+        assert!(cache.is_empty());
+        cache.set(3, |_| Some("d".to_string()));
+        assert_eq!(vec![3], client.cache::<u32, String>().keys());
+        assert_eq!(vec![3], cache.keys());
+    }
+}
@@ -1,24 +1,42 @@
 use crate::{
-    query_executor::{create_executor, synchronize_state},
+    fetch_context::Freshness,
+    query_executor::{create_executor, fetch, synchronize_state},
+    query_hooks::OnQuerySuccess,
+    query_result::{append_item, remove_where, update_where},
+    scheduler::Scheduler,
+    util::KeyMap,
     *,
 };
 use leptos::*;
 use std::{
     any::{Any, TypeId},
     borrow::Borrow,
-    cell::RefCell,
+    cell::{Cell, RefCell},
     collections::hash_map::Entry,
     collections::HashMap,
     future::Future,
     hash::Hash,
     rc::Rc,
+    time::Duration,
 };
 
 /// Provides a Query Client to the current scope.
 pub fn provide_query_client() {
-    provide_context(QueryClient::new(
-        Owner::current().expect("Owner to be present"),
-    ));
+    provide_query_client_with(QueryClientBuilder::new());
+}
+
+/// Provides a Query Client to the current scope, configured with the given [`QueryClientBuilder`].
+///
+/// A client provided below an existing one shadows it for the nested scope, with its own separate cache.
+/// Queries inside and outside of that scope are not shared or de-duplicated, so debug builds log a warning when this happens.
+pub fn provide_query_client_with(builder: QueryClientBuilder) {
+    let client = builder.build(Owner::current().expect("Owner to be present"));
+    if cfg!(debug_assertions) && use_context::<QueryClient>().is_some() {
+        client.logger.warn(
+            "A Query Client was provided below an existing one. Queries in the nested scope use a separate cache, and will not be shared with the rest of the application.",
+        )
+    }
+    provide_context(client);
 }
 
 /// Retrieves a Query Client from the current scope.
@@ -44,20 +62,263 @@ pub struct QueryClient {
     pub(crate) owner: Owner,
     // Signal to indicate a cache entry has been added or removed.
     pub(crate) notify: RwSignal<()>,
-    pub(crate) cache: Rc<RefCell<HashMap<(TypeId, TypeId), Box<dyn CacheEntryTrait>>>>,
+    pub(crate) cache: Rc<RefCell<Cache>>,
+    // The partition the cache belongs to, and the caches of the other partitions.
+    pub(crate) partition: RwSignal<Option<String>>,
+    pub(crate) partitions: Rc<RefCell<HashMap<Option<String>, Cache>>>,
+    // Incremented whenever the cache is replaced, so `QueryCache` handles look up their queries again.
+    pub(crate) cache_generation: Rc<Cell<u64>>,
+    pub(crate) online_manager: OnlineManager,
+    pub(crate) focus_manager: FocusManager,
+    pub(crate) on_query_success: Option<OnQuerySuccess>,
+    pub(crate) logger: Rc<dyn QueryLogger>,
+    // Queries are never stored or shared.
+    pub(crate) no_cache: bool,
+    pub(crate) max_cache_weight: Option<usize>,
+    pub(crate) invalidation_debounce: Option<Duration>,
+    // Interval refetches are paused while hidden, and queries are marked stale once focused again.
+    pub(crate) mark_stale_on_focus: bool,
+    // Debug builds warn when a query is fetched more often than this.
+    pub(crate) max_fetches: Option<usize>,
+    // Debug builds warn when observed queries refetch without their data being read for this long.
+    pub(crate) unread_refetch_after: Option<Duration>,
+    // Fetches skipped for duplicating a fetch that completed moments before.
+    pub(crate) deduplicated_fetches: Rc<Cell<usize>>,
+    // Single timer for all of the client's timed work.
+    pub(crate) scheduler: Scheduler,
+    pub(crate) route_prefetches: PathHandlers<RouteParams>,
+    pub(crate) invalidations: PathHandlers<TagParams>,
+}
+
+pub(crate) type Cache = KeyMap<(TypeId, TypeId), Box<dyn CacheEntryTrait>>;
+
+/// Configures a [`QueryClient`] before it is created.
+///
+/// Example:
+/// ```no_run
+/// use leptos_query::*;
+///
+/// provide_query_client_with(
+///     QueryClientBuilder::new().online_manager(OnlineManager::manual(true)),
+/// );
+/// ```
+#[allow(clippy::type_complexity)]
+#[derive(Default)]
+pub struct QueryClientBuilder {
+    online_manager: Option<OnlineManager>,
+    focus_manager: Option<FocusManager>,
+    on_query_success: Option<OnQuerySuccess>,
+    logger: Option<Rc<dyn QueryLogger>>,
+    no_cache: bool,
+    max_cache_weight: Option<usize>,
+    weighers: Vec<Box<dyn FnOnce(&QueryClient)>>,
+    invalidation_debounce: Option<Duration>,
+    mark_stale_on_focus: bool,
+    max_fetches: Option<usize>,
+    unread_refetch_after: Option<Duration>,
 }
 
-pub(crate) struct CacheEntry<K: 'static, V: 'static>(HashMap<K, Query<K, V>>);
+impl QueryClientBuilder {
+    /// Creates a builder with the default configuration.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Overrides the [`OnlineManager`] used by the client.
+    /// Defaults to [`OnlineManager::new`], which follows the browser's online status.
+    pub fn online_manager(mut self, online_manager: OnlineManager) -> Self {
+        self.online_manager = Some(online_manager);
+        self
+    }
+
+    /// Overrides the [`FocusManager`] used by the client.
+    /// Defaults to [`FocusManager::new`], which follows the browser's visibility & focus events.
+    pub fn focus_manager(mut self, focus_manager: FocusManager) -> Self {
+        self.focus_manager = Some(focus_manager);
+        self
+    }
+
+    /// Sets a callback that is invoked every time a query fetch successfully writes new data to the cache.
+    ///
+    /// Useful for analytics such as "time to data", without instrumenting every call site.
+    ///
+    /// Example:
+    /// ```no_run
+    /// use leptos::*;
+    /// use leptos_query::*;
+    ///
+    /// provide_query_client_with(QueryClientBuilder::new().on_query_success(|success| {
+    ///     logging::log!("{} fetched in {:?}", success.key_type(), success.duration());
+    /// }));
+    /// ```
+    pub fn on_query_success(mut self, callback: impl Fn(&QuerySuccess) + 'static) -> Self {
+        self.on_query_success = Some(Rc::new(callback));
+        self
+    }
+
+    /// Sets the [`QueryLogger`] that receives the client's internal log messages.
+    /// Defaults to [`LeptosLogger`].
+    pub fn logger(mut self, logger: impl QueryLogger + 'static) -> Self {
+        self.logger = Some(Rc::new(logger));
+        self
+    }
+
+    /// Disables caching entirely. Every query fetches fresh data and nothing is shared between usages.
+    ///
+    /// [`set_query_data`](QueryClient::set_query_data) becomes a no-op, as there is no cache to write to.
+    ///
+    /// Useful for component tests and stories, so state doesn't leak between cases.
+    pub fn no_cache(mut self) -> Self {
+        self.no_cache = true;
+        self
+    }
+
+    /// Registers a weigher for queries with key type `K` and value type `V`, used to enforce [`max_cache_weight`](Self::max_cache_weight).
+    ///
+    /// The weight is an arbitrary unit, such as an approximate size in bytes. Queries without a weigher weigh nothing.
+    ///
+    /// Example:
+    /// ```no_run
+    /// use leptos_query::*;
+    ///
+    /// provide_query_client_with(
+    ///     QueryClientBuilder::new()
+    ///         // Roughly 10MB.
+    ///         .max_cache_weight(10_000_000)
+    ///         .weigher::<String, Vec<u8>>(|blob| blob.len())
+    ///         .weigher::<u32, String>(|text| text.len()),
+    /// );
+    /// ```
+    pub fn weigher<K, V>(mut self, weigher: impl Fn(&V) -> usize + 'static) -> Self
+    where
+        K: Clone + Eq + Hash + 'static,
+        V: Clone + 'static,
+    {
+        let weigher: Weigher<V> = Rc::new(weigher);
+        self.weighers.push(Box::new(move |client| {
+            client.use_cache_entry::<K, V, _>(move |entry| entry.weigher = Some(weigher));
+        }));
+        self
+    }
+
+    /// Limits the total weight of cached data, as measured by the registered [weighers](Self::weigher).
+    ///
+    /// When the limit is exceeded, the heaviest queries that are not in use are evicted first,
+    /// so a few large entries are removed before many small ones.
+    pub fn max_cache_weight(mut self, max_weight: usize) -> Self {
+        self.max_cache_weight = Some(max_weight);
+        self
+    }
+
+    /// Waits for the given duration after a query is invalidated before refetching it.
+    ///
+    /// Invalidations of the same query within that window, such as several mutations settling together,
+    /// are coalesced into a single refetch. Without a debounce, invalidated queries are refetched immediately.
+    /// In both cases, invalidations during a fetch cause at most one more refetch.
+    pub fn invalidation_debounce(mut self, debounce: Duration) -> Self {
+        self.invalidation_debounce = Some(debounce);
+        self
+    }
+
+    /// When the app regains focus, marks the queries that went stale while it was hidden as stale, without refetching them.
+    ///
+    /// A query is marked stale when the app was hidden for at least its stale time.
+    /// Its [`is_stale`](crate::QueryResult::is_stale) flag is set right away, and the refetch is deferred until the data is observed again,
+    /// such as when a component using it mounts, or it is refetched or invalidated.
+    /// Interval refetches are paused while the app is hidden, and resume once it is focused again.
+    ///
+    /// Avoids a burst of refetches the instant a tab with many queries regains focus.
+    /// The focus status is provided by the client's [`FocusManager`].
+    pub fn mark_stale_on_focus(mut self) -> Self {
+        self.mark_stale_on_focus = true;
+        self
+    }
+
+    /// In debug builds, warns when a query is fetched more than `max_fetches` times within its stale time, or within a second if it's shorter.
+    ///
+    /// Flags suspicious patterns such as effects that refetch or invalidate a query in a loop, naming the query's key & value types.
+    /// Release builds never warn.
+    pub fn warn_on_overfetch(mut self, max_fetches: usize) -> Self {
+        self.max_fetches = Some(max_fetches);
+        self
+    }
+
+    /// In debug builds, warns when a query in use keeps refetching, but none of its observers has read its [`data`](crate::QueryResult::data) for the given duration.
+    ///
+    /// Points at refetch work that is pure waste, such as interval refetches or invalidations of data that is no longer displayed.
+    /// Warns once per query, until its data is read again. Release builds never warn.
+    pub fn warn_on_unread_refetches(mut self, unread_for: Duration) -> Self {
+        self.unread_refetch_after = Some(unread_for);
+        self
+    }
+
+    /// Creates the Query Client. Internal state is owned by the given owner.
+    pub fn build(self, owner: Owner) -> QueryClient {
+        let client = with_owner(owner, move || QueryClient {
+            notify: create_rw_signal(()),
+            owner,
+            cache: Rc::new(RefCell::new(Cache::default())),
+            partition: create_rw_signal(None),
+            partitions: Rc::default(),
+            cache_generation: Rc::default(),
+            online_manager: self.online_manager.unwrap_or_default(),
+            focus_manager: self.focus_manager.unwrap_or_default(),
+            on_query_success: self.on_query_success,
+            logger: self.logger.unwrap_or_else(|| Rc::new(LeptosLogger)),
+            no_cache: self.no_cache,
+            max_cache_weight: self.max_cache_weight,
+            invalidation_debounce: self.invalidation_debounce,
+            mark_stale_on_focus: self.mark_stale_on_focus,
+            max_fetches: self.max_fetches,
+            unread_refetch_after: self.unread_refetch_after,
+            deduplicated_fetches: Rc::default(),
+            scheduler: Scheduler::default(),
+            route_prefetches: PathHandlers::default(),
+            invalidations: PathHandlers::default(),
+        });
+
+        if client.mark_stale_on_focus {
+            client.watch_focus();
+        }
+
+        for register in self.weighers {
+            register(&client);
+        }
+
+        client
+    }
+}
+
+pub(crate) struct CacheEntry<K: 'static, V: 'static> {
+    queries: Queries<K, V>,
+    weigher: Option<Weigher<V>>,
+}
+
+// Shared, so a typed handle such as QueryCache can hold it without looking it up again.
+pub(crate) type Queries<K, V> = Rc<RefCell<KeyMap<K, Query<K, V>>>>;
+
+type Weigher<V> = Rc<dyn Fn(&V) -> usize>;
+
+impl<K, V> CacheEntry<K, V> {
+    fn new() -> Self {
+        Self {
+            queries: Rc::default(),
+            weigher: None,
+        }
+    }
+}
 
 // Trait to enable cache introspection among distinct cache entry maps.
-pub(crate) trait CacheEntryTrait: CacheSize + CacheInvalidate {
+pub(crate) trait CacheEntryTrait: CacheSize + CacheQueries + CacheWeight {
     fn as_any(&self) -> &dyn Any;
     fn as_any_mut(&mut self) -> &mut dyn Any;
+    // An empty entry for another partition, with the same weigher.
+    fn new_partition(&self) -> Box<dyn CacheEntryTrait>;
 }
 
 impl<K, V> CacheEntryTrait for CacheEntry<K, V>
 where
-    K: Clone,
+    K: Clone + Eq + Hash,
     V: Clone,
 {
     fn as_any(&self) -> &dyn Any {
@@ -67,6 +328,13 @@ where
     fn as_any_mut(&mut self) -> &mut dyn Any {
         self
     }
+
+    fn new_partition(&self) -> Box<dyn CacheEntryTrait> {
+        Box::new(CacheEntry::<K, V> {
+            queries: Rc::default(),
+            weigher: self.weigher.clone(),
+        })
+    }
 }
 
 pub(crate) trait CacheSize {
@@ -75,45 +343,146 @@ pub(crate) trait CacheSize {
 
 impl<K, V> CacheSize for CacheEntry<K, V> {
     fn size(&self) -> usize {
-        self.0.len()
+        RefCell::borrow(&self.queries).len()
     }
 }
 
-pub(crate) trait CacheInvalidate {
-    fn invalidate(&self);
+// Queries are collected while the cache is borrowed, and updated once it is released.
+// Updating a query notifies its observers, whose effects may use the client again.
+pub(crate) trait CacheQueries {
+    fn queries(&self) -> Vec<Box<dyn AnyQuery>>;
 }
 
-impl<K, V> CacheInvalidate for CacheEntry<K, V>
+impl<K, V> CacheQueries for CacheEntry<K, V>
 where
     K: Clone,
     V: Clone,
 {
-    fn invalidate(&self) {
-        for (_, query) in self.0.iter() {
-            query.mark_invalid();
+    fn queries(&self) -> Vec<Box<dyn AnyQuery>> {
+        RefCell::borrow(&self.queries)
+            .values()
+            .map(|query| Box::new(query.clone()) as Box<dyn AnyQuery>)
+            .collect()
+    }
+}
+
+// A query of any type.
+pub(crate) trait AnyQuery {
+    fn mark_invalid(&self) -> bool;
+    fn mark_stale_after_hidden(&self, hidden: Duration) -> bool;
+    fn release(&self);
+    fn is_fetching(&self) -> bool;
+}
+
+impl<K, V> AnyQuery for Query<K, V>
+where
+    K: Clone,
+    V: Clone,
+{
+    fn mark_invalid(&self) -> bool {
+        Query::mark_invalid(self)
+    }
+
+    fn mark_stale_after_hidden(&self, hidden: Duration) -> bool {
+        Query::mark_stale_after_hidden(self, hidden)
+    }
+
+    fn release(&self) {
+        Query::release(self)
+    }
+
+    fn is_fetching(&self) -> bool {
+        Query::is_fetching(self)
+    }
+}
+
+pub(crate) trait CacheWeight {
+    // Total weight of all cached data.
+    fn weight(&self) -> usize;
+    // Weight of the heaviest query that can be evicted.
+    fn heaviest_evictable(&self) -> Option<usize>;
+    // Evicts the heaviest query that can be evicted, returning the freed weight.
+    fn evict_heaviest(&mut self) -> Option<usize>;
+}
+
+impl<K, V> CacheEntry<K, V>
+where
+    K: Clone + Eq + Hash,
+    V: Clone,
+{
+    fn weigh(&self, query: &Query<K, V>) -> usize {
+        match self.weigher {
+            Some(ref weigher) => query
+                .state
+                .with_untracked(|state| state.data().map(|data| weigher(data)))
+                .unwrap_or(0),
+            None => 0,
         }
     }
+
+    // Queries that are not observed or in flight can be evicted.
+    fn heaviest_evictable_query(&self) -> Option<(K, usize)> {
+        RefCell::borrow(&self.queries)
+            .values()
+            .filter(|query| query.observers.get() == 0 && !query.is_fetching())
+            .map(|query| (query.key.clone(), self.weigh(query)))
+            .filter(|(_, weight)| *weight > 0)
+            .max_by_key(|(_, weight)| *weight)
+    }
+}
+
+impl<K, V> CacheWeight for CacheEntry<K, V>
+where
+    K: Clone + Eq + Hash,
+    V: Clone,
+{
+    fn weight(&self) -> usize {
+        RefCell::borrow(&self.queries)
+            .values()
+            .map(|query| self.weigh(query))
+            .sum()
+    }
+
+    fn heaviest_evictable(&self) -> Option<usize> {
+        self.heaviest_evictable_query().map(|(_, weight)| weight)
+    }
+
+    fn evict_heaviest(&mut self) -> Option<usize> {
+        let (key, weight) = self.heaviest_evictable_query()?;
+        let query = self.queries.borrow_mut().remove(&key)?;
+        query.release();
+        Some(weight)
+    }
 }
 
 impl QueryClient {
     /// Creates a new Query Client.
     pub fn new(owner: Owner) -> Self {
-        Self {
-            notify: create_rw_signal(()),
-            owner,
-            cache: Rc::new(RefCell::new(HashMap::new())),
-        }
+        QueryClientBuilder::new().build(owner)
+    }
+
+    /// The [`OnlineManager`] used by this client.
+    pub fn online_manager(&self) -> OnlineManager {
+        self.online_manager
+    }
+
+    /// The [`FocusManager`] used by this client.
+    pub fn focus_manager(&self) -> FocusManager {
+        self.focus_manager
     }
 
     /// Prefetch a query and store it in cache. Returns QueryResult.
-    /// If the entry already exists it will still be refetched.
+    /// If the entry already exists it will still be refetched, unless it was fetched moments ago. See [`deduplicated_fetches`](Self::deduplicated_fetches).
     ///
     /// If you don't need the result opt for [`prefetch_query()`](Self::prefetch_query)
+    ///
+    /// `ssr` determines where the query is fetched, see [`SsrPolicy`]. A `bool` is accepted too:
+    /// `true` for [`ServerAndClient`](SsrPolicy::ServerAndClient), and `false` for [`ClientOnly`](SsrPolicy::ClientOnly).
     pub fn fetch_query<K, V, Fu>(
         &self,
         key: impl Fn() -> K + 'static,
         fetcher: impl Fn(K) -> Fu + 'static,
-        isomorphic: bool,
+        ssr: impl Into<SsrPolicy>,
     ) -> QueryResult<V, impl RefetchFn>
     where
         K: Hash + Eq + Clone + 'static,
@@ -126,20 +495,15 @@ impl QueryClient {
 
         let executor = create_executor(state, fetcher);
 
-        let sync = {
+        ssr.into().create_effect({
             let executor = executor.clone();
             move |_| {
                 let _ = state.get();
                 executor()
             }
-        };
-        if isomorphic {
-            create_isomorphic_effect(sync);
-        } else {
-            create_effect(sync);
-        }
+        });
 
-        synchronize_state(state, executor.clone());
+        synchronize_state(state, executor.clone(), Default::default());
 
         create_query_result(
             state,
@@ -149,14 +513,17 @@ impl QueryClient {
     }
 
     /// Prefetch a query and store it in cache.
-    /// If the entry already exists it will still be refetched.
+    /// If the entry already exists it will still be refetched, unless it was fetched moments ago. See [`deduplicated_fetches`](Self::deduplicated_fetches).
     ///
     /// If you need the result opt for [`fetch_query()`](Self::fetch_query)
+    ///
+    /// `ssr` determines where the query is fetched, see [`SsrPolicy`]. A `bool` is accepted too:
+    /// `true` for [`ServerAndClient`](SsrPolicy::ServerAndClient), and `false` for [`ClientOnly`](SsrPolicy::ClientOnly).
     pub fn prefetch_query<K, V, Fu>(
         &self,
         key: impl Fn() -> K + 'static,
         query: impl Fn(K) -> Fu + 'static,
-        isomorphic: bool,
+        ssr: impl Into<SsrPolicy>,
     ) where
         K: Hash + Eq + Clone + 'static,
         V: Clone + 'static,
@@ -168,17 +535,179 @@ impl QueryClient {
 
         let executor = create_executor(state, query);
 
-        let sync = {
-            move |_| {
-                let _ = state.get();
-                executor()
-            }
-        };
-        if isomorphic {
-            create_isomorphic_effect(sync);
-        } else {
-            create_effect(sync);
+        ssr.into().create_effect(move |_| {
+            let _ = state.get();
+            executor()
+        });
+    }
+
+    /// Registers queries to prefetch when a route is about to be navigated to, see [`prefetch_route`](Self::prefetch_route).
+    ///
+    /// The path uses the same syntax as `leptos_router`: `:name` matches a single segment, and a trailing `*name` matches the rest of the path.
+    /// The prefetch receives the matched [`RouteParams`], to derive the query keys from.
+    /// It's unregistered once the current reactive scope is disposed, so a prefetch registered by a component only applies while it's mounted.
+    ///
+    /// Example:
+    /// ```no_run
+    /// use leptos::*;
+    /// use leptos_query::*;
+    /// # #[derive(Clone)]
+    /// # struct Monkey;
+    /// # async fn get_monkey(id: u32) -> Monkey { todo!() }
+    ///
+    /// provide_query_client();
+    /// let client = use_query_client();
+    ///
+    /// client.register_route_prefetch("/monkeys/:id", |client, params| {
+    ///     if let Some(id) = params.get("id").and_then(|id| id.parse::<u32>().ok()) {
+    ///         client.prefetch_query(move || id, get_monkey, false);
+    ///     }
+    /// });
+    /// ```
+    pub fn register_route_prefetch(
+        &self,
+        path: impl Into<String>,
+        prefetch: impl Fn(&QueryClient, &RouteParams) + 'static,
+    ) -> &Self {
+        self.route_prefetches
+            .register(path.into(), Rc::new(prefetch));
+        self
+    }
+
+    /// Runs the prefetches registered for every route that matches the path, with [`register_route_prefetch`](Self::register_route_prefetch).
+    ///
+    /// Call it when a route is about to be navigated to, such as when a link is hovered, or once the route is matched.
+    /// Query strings & fragments of the path are ignored.
+    ///
+    /// Returns the number of prefetches that were run.
+    ///
+    /// Example:
+    /// ```no_run
+    /// use leptos::*;
+    /// use leptos_query::*;
+    ///
+    /// let client = use_query_client();
+    ///
+    /// view! {
+    ///     <a href="/monkeys/1" on:mouseenter=move |_| { client.prefetch_route("/monkeys/1"); }>
+    ///         "Monkey"
+    ///     </a>
+    /// };
+    /// ```
+    pub fn prefetch_route(&self, path: &str) -> usize {
+        let prefetches = self.route_prefetches.matching(path);
+        for (prefetch, params) in prefetches.iter() {
+            prefetch(self, params);
+        }
+        prefetches.len()
+    }
+
+    /// Registers the queries to invalidate for a tag, see [`invalidate_tag`](Self::invalidate_tag).
+    ///
+    /// Keeps the relationships between mutations & queries in one place, rather than scattered across mutation call sites.
+    /// Tags use the same syntax as [route prefetches](Self::register_route_prefetch): `:name` matches a single segment,
+    /// and a trailing `*name` matches the rest of the tag. The invalidation receives the matched [`TagParams`].
+    /// It's unregistered once the current reactive scope is disposed, so an invalidation registered by a component only applies while it's mounted.
+    ///
+    /// Example:
+    /// ```no_run
+    /// use leptos::*;
+    /// use leptos_query::*;
+    /// # #[derive(Clone, Hash, PartialEq, Eq)]
+    /// # struct PostId(u32);
+    /// # #[derive(Clone, Hash, PartialEq, Eq)]
+    /// # struct PostsList;
+    /// # #[derive(Clone)]
+    /// # struct Post;
+    ///
+    /// provide_query_client();
+    /// let client = use_query_client();
+    ///
+    /// client.register_invalidation("post/:id", |client, params| {
+    ///     if let Some(id) = params.get("id").and_then(|id| id.parse::<u32>().ok()) {
+    ///         client.invalidate_query::<PostId, Post>(PostId(id));
+    ///     }
+    ///     client.invalidate_query::<PostsList, Vec<Post>>(PostsList);
+    /// });
+    ///
+    /// // After a mutation of post 1.
+    /// client.invalidate_tag("post/1");
+    /// ```
+    pub fn register_invalidation(
+        &self,
+        tag: impl Into<String>,
+        invalidate: impl Fn(&QueryClient, &TagParams) + 'static,
+    ) -> &Self {
+        self.invalidations.register(tag.into(), Rc::new(invalidate));
+        self
+    }
+
+    /// Runs the invalidations registered for every tag pattern that matches the tag, with [`register_invalidation`](Self::register_invalidation).
+    ///
+    /// Call it once a mutation completes, with the tag of what it changed.
+    ///
+    /// Returns the number of invalidations that were run.
+    pub fn invalidate_tag(&self, tag: &str) -> usize {
+        let invalidations = self.invalidations.matching(tag);
+        for (invalidate, params) in invalidations.iter() {
+            invalidate(self, params);
         }
+        invalidations.len()
+    }
+
+    /// Refetches an existing query once, using the given fetcher instead of the query's own.
+    ///
+    /// Useful for passing extra parameters to a single refetch, such as bypassing a server side cache,
+    /// without redefining the query. The result is stored in the cache like any other fetch.
+    ///
+    /// Returns `true` if a refetch was started. Nothing happens if the query doesn't exist,
+    /// is already fetching, or the client is offline.
+    ///
+    /// Example:
+    /// ```no_run
+    /// use leptos::*;
+    /// use leptos_query::*;
+    /// # #[derive(Clone)]
+    /// # struct Monkey;
+    /// # async fn get_monkey(id: u32, force: bool) -> Monkey { todo!() }
+    ///
+    /// let client = use_query_client();
+    /// client.refetch_query_with::<u32, Monkey, _>(1, |id| get_monkey(id, true));
+    ///
+    /// ```
+    pub fn refetch_query_with<K, V, Fu>(
+        &self,
+        key: impl Borrow<K>,
+        fetcher: impl FnOnce(K) -> Fu + 'static,
+    ) -> bool
+    where
+        K: Hash + Eq + Clone + 'static,
+        V: Clone + 'static,
+        Fu: Future<Output = V> + 'static,
+    {
+        if !self.online_manager.is_online() {
+            return false;
+        }
+
+        let query = self
+            .use_cache_option(|cache: &KeyMap<K, Query<K, V>>| cache.get(key.borrow()).cloned());
+        let Some(query) = query else {
+            return false;
+        };
+
+        let next_state = match query.state.get_untracked() {
+            QueryState::Fetching(_) | QueryState::Loading | QueryState::Idle => return false,
+            QueryState::Created => QueryState::Loading,
+            QueryState::Loaded(data) | QueryState::Invalid(data) => QueryState::Fetching(data),
+        };
+        query.set_state(next_state);
+
+        let client = self.clone();
+        spawn_local(async move {
+            fetch(&client, &query, fetcher).await;
+        });
+
+        true
     }
 
     /// Retrieve the current state for an existing query.
@@ -197,7 +726,7 @@ impl QueryClient {
         let maybe_query = create_memo(move |_| {
             let key = key();
             client.notify.get();
-            client.use_cache_option(|cache: &HashMap<K, Query<K, V>>| cache.get(&key).cloned())
+            client.use_cache_option(|cache: &KeyMap<K, Query<K, V>>| cache.get(&key).cloned())
         });
 
         synchronize_observer(maybe_query.into());
@@ -205,13 +734,84 @@ impl QueryClient {
         Signal::derive(move || maybe_query.get().map(|s| s.state.get()))
     }
 
+    /// Invokes the callback with the state of the query every time it changes, until the returned [`QueryWatcher`] is dropped.
+    ///
+    /// Useful for bridging query data into code outside of Leptos, such as charting libraries.
+    /// The callback is invoked right away if the query has a state other than [`Created`](QueryState::Created).
+    /// If the query is created later, or removed and created again, it is watched as well.
+    /// The transient `Created` state is skipped, and each write of the state is reported once.
+    /// Watching a query doesn't count as observing it, so it doesn't keep the query in the cache.
+    ///
+    /// Example:
+    /// ```no_run
+    /// use leptos::*;
+    /// use leptos_query::*;
+    ///
+    /// let client = use_query_client();
+    /// let watcher = client.watch_query::<u32, Vec<f64>>(&1, |state| {
+    ///     if let Some(points) = state.data() {
+    ///         // Redraw the chart.
+    ///     }
+    /// });
+    ///
+    /// // Later, when the chart is destroyed.
+    /// watcher.unsubscribe();
+    /// ```
+    pub fn watch_query<K, V>(
+        &self,
+        key: &K,
+        callback: impl Fn(&QueryState<V>) + 'static,
+    ) -> QueryWatcher
+    where
+        K: Hash + Eq + Clone + 'static,
+        V: Clone + 'static,
+    {
+        let client = self.clone();
+        let key = key.clone();
+        let callback = Rc::new(callback);
+        let watch = move |_| {
+            let (client, key, callback) = (client.clone(), key.clone(), callback.clone());
+            // Compared by signal, so a query that was removed and created again is watched anew.
+            let query = create_memo(move |_| {
+                client.notify.get();
+                client.use_cache_option(|cache: &KeyMap<K, Query<K, V>>| {
+                    cache
+                        .get(&key)
+                        .map(|query| (query.state, query.version.clone()))
+                })
+            });
+            // Reports each written version of the state once, skipping the transient Created state.
+            create_isomorphic_effect(move |last: Option<Option<_>>| {
+                let last = last.flatten();
+                let Some((signal, version)) = query.get() else {
+                    return last;
+                };
+                let state = signal.get();
+                let current = (signal, version.get());
+                if let QueryState::Created = state {
+                    return Some(current);
+                }
+                if last.as_ref() != Some(&current) {
+                    untrack(|| callback(&state));
+                }
+                Some(current)
+            });
+        };
+        let ((), disposer) = with_owner(self.owner, || as_child_of_current_owner(watch)(()));
+
+        QueryWatcher {
+            _disposer: disposer,
+        }
+    }
+
     /// Attempts to invalidate an entry in the Query Cache.
     /// Matching query is marked as invalid, and will be refetched in background once it's active.
     ///
     /// Returns true if the entry was successfully invalidated.
     ///
     /// Example:
-    /// ```
+    /// ```no_run
+    /// # use leptos_query::*;
     /// let client = use_query_client();
     /// let invalidated = client.invalidate_query::<u32, u32>(0);
     /// ```
@@ -220,12 +820,10 @@ impl QueryClient {
         K: Hash + Eq + Clone + 'static,
         V: Clone + 'static,
     {
-        self.use_cache_option(|cache: &HashMap<K, Query<K, V>>| {
-            cache
-                .get(Borrow::borrow(&key))
-                .map(|state| state.mark_invalid())
+        self.use_cache_option(|cache: &KeyMap<K, Query<K, V>>| {
+            cache.get(Borrow::borrow(&key)).cloned()
         })
-        .unwrap_or(false)
+        .is_some_and(|query| query.mark_invalid())
     }
 
     /// Attempts to invalidate multiple entries in the Query Cache with a common <K, V> type.
@@ -234,10 +832,11 @@ impl QueryClient {
     /// Returns the keys that were successfully invalidated.
     ///
     /// Example:
-    /// ```
+    /// ```no_run
+    /// # use leptos_query::*;
     /// let client = use_query_client();
     /// let keys: Vec<u32> = vec![0, 1];
-    /// let invalidated = client.invalidate_queries::<u32, u32, _>(keys)
+    /// let invalidated = client.invalidate_queries::<u32, u32, _>(keys);
     ///
     /// ```
     pub fn invalidate_queries<K, V, Q>(&self, keys: impl IntoIterator<Item = Q>) -> Option<Vec<Q>>
@@ -247,19 +846,20 @@ impl QueryClient {
         Q: Borrow<K>,
     {
         // Find all states, drop borrow, then mark invalid.
-        let cache_borrowed = RefCell::borrow(&self.cache);
-        let type_key = (TypeId::of::<K>(), TypeId::of::<V>());
-        let cache = cache_borrowed.get(&type_key)?;
-        let cache = cache.as_any().downcast_ref::<CacheEntry<K, V>>()?;
+        let keys = keys.into_iter().collect::<Vec<_>>();
+        let queries = self.use_cache_option(|cache: &KeyMap<K, Query<K, V>>| {
+            let queries = keys
+                .iter()
+                .map(|key| cache.get(Borrow::borrow(key)).cloned())
+                .collect::<Vec<_>>();
+            Some(queries)
+        })?;
+
         let result = keys
             .into_iter()
-            .filter(|key| {
-                cache
-                    .0
-                    .get(Borrow::borrow(key))
-                    .map(|query| query.mark_invalid())
-                    .unwrap_or(false)
-            })
+            .zip(queries)
+            .filter(|(_, query)| query.as_ref().is_some_and(|query| query.mark_invalid()))
+            .map(|(key, _)| key)
             .collect::<Vec<_>>();
 
         Some(result)
@@ -268,9 +868,11 @@ impl QueryClient {
     /// Invalidate all queries with a common <K, V> type.
     ///
     /// Example:
-    /// ```
+    /// ```no_run
     /// use leptos::*;
     /// use leptos_query::*;
+    /// # #[derive(Clone)]
+    /// # struct Monkey;
     ///
     /// let client = use_query_client();
     /// client.invalidate_query_type::<String, Monkey>();
@@ -281,12 +883,12 @@ impl QueryClient {
         K: Clone + 'static,
         V: Clone + 'static,
     {
-        self.use_cache_option(|cache: &HashMap<K, Query<K, V>>| {
-            for q in cache.values() {
-                q.mark_invalid();
-            }
-            Some(())
+        let queries = self.use_cache_option(|cache: &KeyMap<K, Query<K, V>>| {
+            Some(cache.values().cloned().collect::<Vec<_>>())
         });
+        for query in queries.into_iter().flatten() {
+            query.mark_invalid();
+        }
 
         self
     }
@@ -295,7 +897,7 @@ impl QueryClient {
     ///
     /// Example:
     ///
-    /// ```
+    /// ```no_run
     /// use leptos::*;
     /// use leptos_query::*;
     ///
@@ -305,16 +907,106 @@ impl QueryClient {
     /// ```
     ///
     pub fn invalidate_all_queries(&self) -> &Self {
-        for cache in RefCell::borrow(&self.cache).values() {
-            cache.invalidate();
+        for query in self.all_queries() {
+            query.mark_invalid();
         }
         self
     }
 
-    /// Returns the current size of the cache.
+    /// Switches the cache to the given partition, such as the id of the logged in user.
+    ///
+    /// Each partition has its own cache, so the same key fetched for different users never collides.
+    /// Queries in use switch to the partition's entries right away, and fetch them if needed.
+    /// The cache of the previous partition is kept as is, so switching back to it is cheap.
+    /// Use [`remove_partition`](Self::remove_partition) to drop it, such as when the user logs out.
     ///
     /// Example:
+    /// ```no_run
+    /// use leptos::*;
+    /// use leptos_query::*;
+    /// # fn logged_in_user_id() -> u32 { todo!() }
+    ///
+    /// let client = use_query_client();
+    /// client.set_partition(logged_in_user_id().to_string());
     /// ```
+    pub fn set_partition(&self, partition: impl Into<String>) {
+        self.switch_partition(Some(partition.into()));
+    }
+
+    /// Switches the cache back to the default partition, used before any partition is set.
+    /// See [`set_partition`](Self::set_partition).
+    pub fn clear_partition(&self) {
+        self.switch_partition(None);
+    }
+
+    /// The partition the cache currently belongs to. [`None`](Option::None) for the default partition.
+    pub fn partition(&self) -> Signal<Option<String>> {
+        self.partition.into()
+    }
+
+    /// Drops the cache of a partition that isn't in use, disposing its queries.
+    /// Returns `false` if the partition is the current one, or has no cache.
+    ///
+    /// Existing [`QueryCache`](crate::QueryCache) handles and queries in use don't need to be created again.
+    /// They follow the current partition, so switching to a removed partition later gives them its new, empty cache.
+    pub fn remove_partition(&self, partition: &str) -> bool {
+        let partition = Some(partition.to_string());
+        if self
+            .partition
+            .with_untracked(|current| *current == partition)
+        {
+            return false;
+        }
+        let removed = self.partitions.borrow_mut().remove(&partition);
+        match removed {
+            Some(cache) => {
+                self.cache_generation.set(self.cache_generation.get() + 1);
+                for query in cache.values().flat_map(|cache| cache.queries()) {
+                    query.release();
+                }
+                true
+            }
+            None => false,
+        }
+    }
+
+    fn switch_partition(&self, partition: Option<String>) {
+        let previous = self.partition.get_untracked();
+        if previous == partition {
+            return;
+        }
+        {
+            let mut partitions = self.partitions.borrow_mut();
+            let mut cache = self.cache.borrow_mut();
+            let next = partitions.remove(&partition).unwrap_or_else(|| {
+                cache
+                    .iter()
+                    .map(|(type_key, cache)| (*type_key, cache.new_partition()))
+                    .collect()
+            });
+            partitions.insert(previous, std::mem::replace(&mut *cache, next));
+        }
+        self.cache_generation.set(self.cache_generation.get() + 1);
+        // Both signals are set once the cache is released, as observers will look up their queries again.
+        self.partition.set(partition);
+        self.notify.set(());
+    }
+
+    /// The number of fetches that were skipped for duplicating a fetch of the same query that completed moments before.
+    ///
+    /// Effects can run in quick succession, such as when components mount twice in development, or while hydrating.
+    /// A query whose data was fetched within the last few milliseconds isn't fetched again by them.
+    /// Explicit refetches, such as [`QueryResult::refetch`], and refetches of invalidated data are never skipped.
+    ///
+    /// Useful to verify in tests that a query isn't fetched twice.
+    pub fn deduplicated_fetches(&self) -> usize {
+        self.deduplicated_fetches.get()
+    }
+
+    /// Returns the current size of the cache.
+    ///
+    /// Example:
+    /// ```no_run
     /// use leptos::*;
     /// use leptos_query::*;
     ///
@@ -333,6 +1025,244 @@ impl QueryClient {
         .into()
     }
 
+    /// Returns the approximate total weight of all cached data, as measured by the weighers registered with [`QueryClientBuilder::weigher`].
+    /// Data of types without a weigher is not counted.
+    ///
+    /// This is not reactive. Read it on demand, for example when rendering a diagnostics screen.
+    ///
+    /// Example:
+    /// ```no_run
+    /// use leptos::*;
+    /// use leptos_query::*;
+    ///
+    /// let client = use_query_client();
+    /// let estimated_size = client.estimated_size();
+    ///
+    /// ```
+    pub fn estimated_size(&self) -> usize {
+        RefCell::borrow(&self.cache)
+            .values()
+            .map(|entry| entry.weight())
+            .sum()
+    }
+
+    /// Evicts the heaviest queries that are not in use, until the [estimated size](Self::estimated_size) of the cache is at most `max_weight`.
+    ///
+    /// Returns the estimated size after trimming.
+    /// Queries that are being observed or fetched are never evicted, so the result may still exceed `max_weight`.
+    pub fn trim_cache(&self, max_weight: usize) -> usize {
+        let (weight, evicted) = {
+            let mut cache = self.cache.borrow_mut();
+            let mut weight: usize = cache.values().map(|entry| entry.weight()).sum();
+            let mut evicted = false;
+            while weight > max_weight {
+                let heaviest = cache
+                    .values_mut()
+                    .filter_map(|entry| entry.heaviest_evictable().map(|w| (w, entry)))
+                    .max_by_key(|(weight, _)| *weight);
+
+                match heaviest.and_then(|(_, entry)| entry.evict_heaviest()) {
+                    Some(freed) => {
+                        weight -= freed;
+                        evicted = true;
+                    }
+                    None => break,
+                }
+            }
+            (weight, evicted)
+        };
+
+        if evicted {
+            self.notify.set(());
+        }
+
+        weight
+    }
+
+    /// Returns a snapshot of every cached query with key type `K` and value type `V`, along with its current state.
+    ///
+    /// Useful for exporting the cache, debugging panels, or custom garbage collection.
+    /// The snapshot is not reactive, and entries are returned in no particular order.
+    ///
+    /// Example:
+    /// ```no_run
+    /// use leptos::*;
+    /// use leptos_query::*;
+    /// # #[derive(Clone)]
+    /// # struct Monkey;
+    ///
+    /// let client = use_query_client();
+    /// let cached_monkeys = client
+    ///     .iter_entries::<String, Monkey>()
+    ///     .into_iter()
+    ///     .filter_map(|(id, state)| state.data().cloned().map(|monkey| (id, monkey)));
+    ///
+    /// ```
+    pub fn iter_entries<K, V>(&self) -> Vec<(K, QueryState<V>)>
+    where
+        K: Clone + 'static,
+        V: Clone + 'static,
+    {
+        self.use_cache_option(|cache: &KeyMap<K, Query<K, V>>| {
+            Some(
+                cache
+                    .iter()
+                    .map(|(key, query)| (key.clone(), query.state.get_untracked()))
+                    .collect(),
+            )
+        })
+        .unwrap_or_default()
+    }
+
+    /// The queries with key type `K` and value type `V`, so a sequence of operations on them doesn't repeat the types.
+    /// See [`QueryCache`].
+    pub fn cache<K, V>(&self) -> QueryCache<K, V>
+    where
+        K: Hash + Eq + Clone + 'static,
+        V: Clone + 'static,
+    {
+        QueryCache::new(self.clone())
+    }
+
+    /// Returns the options an existing query is currently using, after merging the options of all of its observers.
+    ///
+    /// Useful for debugging queries that are used with different [`QueryOptions`].
+    pub fn get_query_options<K, V>(&self, key: impl Borrow<K>) -> Option<EffectiveQueryOptions>
+    where
+        K: Hash + Eq + Clone + 'static,
+        V: Clone + 'static,
+    {
+        self.use_cache_option(|cache: &KeyMap<K, Query<K, V>>| {
+            cache
+                .get(key.borrow())
+                .map(|query| query.effective_options())
+        })
+    }
+
+    /// Overrides the stale time of an existing query's current data, such as from an `expires_in` field in the response.
+    ///
+    /// Applies only to this cache entry, and takes precedence over the [`QueryOptions`] of its observers until the next fetch.
+    /// [`None`](Option::None) means the data never becomes stale. Returns `false` if the query doesn't exist.
+    /// To set it from within the fetcher, use [`FetchContext::set_stale_time`].
+    ///
+    /// Example:
+    /// ```no_run
+    /// use leptos::*;
+    /// use leptos_query::*;
+    /// use std::time::Duration;
+    /// # #[derive(Clone, serde::Deserialize, serde::Serialize)]
+    /// # struct Token { expires_in: u64 }
+    /// # async fn get_token(id: u32) -> Token { todo!() }
+    ///
+    /// let query = use_query(
+    ///     || 1,
+    ///     get_token,
+    ///     QueryOptions::builder()
+    ///         .on_success(|token: &Token| {
+    ///             let stale_time = Duration::from_secs(token.expires_in);
+    ///             use_query_client().set_query_stale_time::<u32, Token>(1, Some(stale_time));
+    ///         })
+    ///         .build(),
+    /// );
+    /// ```
+    pub fn set_query_stale_time<K, V>(
+        &self,
+        key: impl Borrow<K>,
+        stale_time: Option<Duration>,
+    ) -> bool
+    where
+        K: Hash + Eq + Clone + 'static,
+        V: Clone + 'static,
+    {
+        self.update_query_freshness::<K, V>(key, |freshness| {
+            freshness.stale_time = Some(stale_time)
+        })
+    }
+
+    /// Overrides the cache time of an existing query's current data.
+    ///
+    /// Applies only to this cache entry, and takes precedence over the [`QueryOptions`] of its observers until the next fetch.
+    /// [`None`](Option::None) means the data is never evicted from the cache. Returns `false` if the query doesn't exist.
+    /// To set it from within the fetcher, use [`FetchContext::set_cache_time`].
+    pub fn set_query_cache_time<K, V>(
+        &self,
+        key: impl Borrow<K>,
+        cache_time: Option<Duration>,
+    ) -> bool
+    where
+        K: Hash + Eq + Clone + 'static,
+        V: Clone + 'static,
+    {
+        self.update_query_freshness::<K, V>(key, |freshness| {
+            freshness.cache_time = Some(cache_time)
+        })
+    }
+
+    fn update_query_freshness<K, V>(
+        &self,
+        key: impl Borrow<K>,
+        update: impl FnOnce(&mut Freshness),
+    ) -> bool
+    where
+        K: Hash + Eq + Clone + 'static,
+        V: Clone + 'static,
+    {
+        let query = self
+            .use_cache_option(|cache: &KeyMap<K, Query<K, V>>| cache.get(key.borrow()).cloned());
+        let Some(query) = query else {
+            return false;
+        };
+        let mut freshness = query.freshness.get();
+        update(&mut freshness);
+        query.set_freshness(freshness);
+        true
+    }
+
+    /// Attaches metadata to an existing query, replacing any metadata set through [`QueryOptions::meta`].
+    ///
+    /// The metadata is available to the query's fetcher through [`FetchContext`].
+    /// Returns `false` if the query doesn't exist.
+    ///
+    /// Example:
+    /// ```no_run
+    /// use leptos::*;
+    /// use leptos_query::*;
+    /// # #[derive(Clone)]
+    /// # struct Monkey;
+    ///
+    /// let client = use_query_client();
+    /// client.set_query_meta::<u32, Monkey>(1, QueryMeta::new("imported"));
+    ///
+    /// let source = client
+    ///     .get_query_meta::<u32, Monkey>(1)
+    ///     .and_then(|meta| meta.get::<&str>().copied());
+    /// ```
+    pub fn set_query_meta<K, V>(&self, key: impl Borrow<K>, meta: QueryMeta) -> bool
+    where
+        K: Hash + Eq + Clone + 'static,
+        V: Clone + 'static,
+    {
+        self.use_cache_option(|cache: &KeyMap<K, Query<K, V>>| {
+            cache
+                .get(key.borrow())
+                .map(|query| query.meta.set(Some(meta)))
+        })
+        .is_some()
+    }
+
+    /// Returns the metadata of a query, if it exists and has any.
+    pub fn get_query_meta<K, V>(&self, key: impl Borrow<K>) -> Option<QueryMeta>
+    where
+        K: Hash + Eq + Clone + 'static,
+        V: Clone + 'static,
+    {
+        self.use_cache_option(|cache: &KeyMap<K, Query<K, V>>| {
+            cache
+                .get(key.borrow())
+                .and_then(|query| query.meta.get_untracked())
+        })
+    }
+
     /// A synchronous function that can be used to immediately set a query's data.
     ///
     /// If the query does not exist, it will be created.
@@ -344,20 +1274,22 @@ impl QueryClient {
     /// If the updater function receives [`None`](Option::None) as input, you can return [`None`](Option::None) to bail out of the update and thus not create a new cache entry.
     ///
     /// Example:
-    /// ```
+    /// ```no_run
     /// use leptos::*;
     /// use leptos_query::*;
+    /// # #[derive(Clone)]
+    /// # struct Monkey;
     ///
     /// let client = use_query_client();
     /// let new_monkey: Monkey = todo!();
     ///
     /// // Overwrites existing cache data.
-    /// client.set_query_data::<u32, Monkey>(1, |_| Some(new_monkey));
+    /// client.set_query_data::<u32, Monkey>(1, |_| Some(new_monkey.clone()));
     ///
     /// // Only updates if query data exists.
     /// client.set_query_data::<u32, Monkey>(1, |maybe_monkey| {
     ///     let prev_monkey = maybe_monkey?;
-    ///     new_monkey
+    ///     Some(new_monkey)
     /// });
     /// ```
     pub fn set_query_data<K, V>(
@@ -369,111 +1301,331 @@ impl QueryClient {
         K: Clone + Eq + Hash + 'static,
         V: Clone + 'static,
     {
-        enum SetResult {
-            Inserted,
-            Updated,
-            Nothing,
+        if self.no_cache {
+            return self;
         }
-        let result = self.use_cache(move |(owner, cache)| {
-            match cache.entry(key.clone()) {
-                Entry::Occupied(entry) => {
-                    let query = entry.get();
-                    let result = query.state.with_untracked(|s| {
-                        let data = s.query_data().map(|d| &d.data);
-                        updater(data)
-                    });
-                    // Only update query data if updater returns Some.
-                    if let Some(result) = result {
-                        query.state.set(QueryState::Loaded(QueryData::now(result)));
-                        SetResult::Updated
-                    } else {
-                        SetResult::Nothing
-                    }
+
+        // The updater runs while nothing is borrowed, so it can use the client & the query.
+        let query =
+            self.use_cache_option(|cache: &KeyMap<K, Query<K, V>>| cache.get(&key).cloned());
+        let data = query
+            .as_ref()
+            .and_then(|query| query.state.with_untracked(|s| s.data().cloned()));
+
+        // Only insert or update query if updater returns Some.
+        let Some(result) = updater(data.as_ref()) else {
+            return self;
+        };
+        let query = match query {
+            Some(query) => query,
+            None => self.get_or_create_query::<K, V>(key).0,
+        };
+        query.set_state(QueryState::Loaded(QueryData::now(result)));
+        self.enforce_max_cache_weight();
+
+        self
+    }
+
+    /// Like [`set_query_data`](Self::set_query_data), but only writes if the query hasn't changed since it was read.
+    ///
+    /// `based_on` is the [version](Self::get_query_version) of the query the update was derived from, or [`None`](Option::None) if there was no query.
+    /// If the query has since been written to by a fetch or another update, nothing is written and a [`QueryConflict`] is returned,
+    /// instead of silently overwriting the newer data.
+    ///
+    /// Example:
+    /// ```no_run
+    /// use leptos::*;
+    /// use leptos_query::*;
+    /// # #[derive(Clone)]
+    /// # struct Monkey { name: String }
+    ///
+    /// let client = use_query_client();
+    /// let based_on = client.get_query_version::<u32, Monkey>(1);
+    ///
+    /// // Later, after an edit is saved.
+    /// let result = client.try_set_query_data::<u32, Monkey>(1, based_on, |monkey| {
+    ///     let mut monkey = monkey?.clone();
+    ///     monkey.name = "George".to_string();
+    ///     Some(monkey)
+    /// });
+    ///
+    /// if let Err(conflict) = result {
+    ///     // Someone else updated the monkey. Refetch or ask the user to resolve.
+    /// }
+    /// ```
+    pub fn try_set_query_data<K, V>(
+        &self,
+        key: K,
+        based_on: Option<u64>,
+        updater: impl FnOnce(Option<&V>) -> Option<V> + 'static,
+    ) -> Result<&Self, QueryConflict>
+    where
+        K: Clone + Eq + Hash + 'static,
+        V: Clone + 'static,
+    {
+        let actual = self.get_query_version::<K, V>(&key);
+
+        if actual != based_on {
+            return Err(QueryConflict {
+                expected: based_on,
+                actual,
+            });
+        }
+
+        Ok(self.set_query_data(key, updater))
+    }
+
+    /// The version of a query, which changes on every write to it, such as fetches, updates, and invalidations.
+    /// Returns [`None`](Option::None) if the query isn't cached.
+    ///
+    /// See [`try_set_query_data`](Self::try_set_query_data).
+    pub fn get_query_version<K, V>(&self, key: impl Borrow<K>) -> Option<u64>
+    where
+        K: Eq + Hash + 'static,
+        V: 'static,
+    {
+        self.use_cache_option(|cache: &KeyMap<K, Query<K, V>>| {
+            cache.get(key.borrow()).map(|query| query.version.get())
+        })
+    }
+
+    /// Appends an item to the end of a list query's data, if the query has data.
+    /// See [`QueryResult::append_item`] for editing the list of a result.
+    ///
+    /// Example:
+    /// ```no_run
+    /// use leptos::*;
+    /// use leptos_query::*;
+    /// # #[derive(Clone)]
+    /// # struct Todo { id: u32, done: bool }
+    ///
+    /// let client = use_query_client();
+    ///
+    /// client.append_query_item::<(), Todo>((), Todo { id: 3, done: false });
+    /// client.update_query_items::<(), Todo>((), |todo| todo.id == 3, |todo| todo.done = true);
+    /// client.remove_query_items::<(), Todo>((), |todo| todo.done);
+    /// ```
+    pub fn append_query_item<K, T>(&self, key: K, item: T) -> &Self
+    where
+        K: Clone + Eq + Hash + 'static,
+        T: Clone + 'static,
+    {
+        self.set_query_data(key, append_item(item))
+    }
+
+    /// Removes the items matching the predicate from a list query's data. Returns the number of removed items.
+    /// Observers are only notified if an item was removed.
+    pub fn remove_query_items<K, T>(
+        &self,
+        key: K,
+        predicate: impl Fn(&T) -> bool + 'static,
+    ) -> usize
+    where
+        K: Clone + Eq + Hash + 'static,
+        T: Clone + 'static,
+    {
+        let removed = Rc::new(Cell::new(0));
+        self.set_query_data(key, remove_where(predicate, removed.clone()));
+        removed.get()
+    }
+
+    /// Updates the items matching the predicate of a list query's data in place. Returns the number of updated items.
+    /// Observers are only notified if an item was updated.
+    pub fn update_query_items<K, T>(
+        &self,
+        key: K,
+        predicate: impl Fn(&T) -> bool + 'static,
+        update: impl FnMut(&mut T) + 'static,
+    ) -> usize
+    where
+        K: Clone + Eq + Hash + 'static,
+        T: Clone + 'static,
+    {
+        let updated = Rc::new(Cell::new(0));
+        self.set_query_data(key, update_where(predicate, update, updated.clone()));
+        updated.get()
+    }
+
+    /// Seeds a query with data that was already resolved elsewhere, such as by a route's loader,
+    /// so components using the query find warm data immediately instead of fetching.
+    ///
+    /// Call it before the components using the query mount, for example at the top of the route's view.
+    /// The data keeps its own [`updated_at`](QueryData::updated_at), so it is subject to `stale_time` from the moment it was loaded.
+    /// If the query already has data that is as recent, nothing is written.
+    ///
+    /// Returns true if the data was written.
+    ///
+    /// Example:
+    /// ```no_run
+    /// use leptos::*;
+    /// use leptos_query::*;
+    /// use std::time::Duration;
+    /// # #[derive(Clone, serde::Deserialize, serde::Serialize)]
+    /// # struct Monkey { name: String }
+    /// # async fn get_monkey(id: u32) -> Monkey { todo!() }
+    ///
+    /// #[component]
+    /// fn MonkeyPage(id: u32, loaded: Monkey) -> impl IntoView {
+    ///     // Data resolved by the route's loader.
+    ///     use_query_client().seed_query_data(id, QueryData::now(loaded));
+    ///
+    ///     view! { <MonkeyName id=id/> }
+    /// }
+    ///
+    /// #[component]
+    /// fn MonkeyName(id: u32) -> impl IntoView {
+    ///     // Doesn't fetch while the seeded data is fresh.
+    ///     let query = use_query(
+    ///         move || id,
+    ///         get_monkey,
+    ///         QueryOptions::stale_time(Duration::from_secs(30)),
+    ///     );
+    ///     view! { <p>{move || query.data.get().map(|monkey| monkey.name)}</p> }
+    /// }
+    /// ```
+    pub fn seed_query_data<K, V>(&self, key: K, data: QueryData<V>) -> bool
+    where
+        K: Clone + Eq + Hash + 'static,
+        V: Clone + 'static,
+    {
+        if self.no_cache {
+            return false;
+        }
+
+        let (query, _) = self.get_or_create_query::<K, V>(key);
+        let next_state = match query.state.get_untracked() {
+            state if state.updated_at() >= Some(data.updated_at) => return false,
+            QueryState::Loading | QueryState::Fetching(_) => QueryState::Fetching(data),
+            _ => QueryState::Loaded(data),
+        };
+        query.set_state(next_state);
+        self.enforce_max_cache_weight();
+
+        true
+    }
+
+    // Marks the queries that went stale while the app was hidden, once it is focused again.
+    fn watch_focus(&self) {
+        let client = self.clone();
+        let focused = self.focus_manager.focused();
+        with_owner(self.owner, move || {
+            create_isomorphic_effect(move |hidden_at: Option<Option<Instant>>| {
+                if !focused.get() {
+                    return hidden_at.flatten().or_else(|| Some(Instant::now()));
                 }
-                Entry::Vacant(entry) => {
-                    // Only insert query if updater returns Some.
-                    if let Some(result) = updater(None) {
-                        let query = with_owner(owner, || Query::new(key));
-                        query.state.set(QueryState::Loaded(QueryData::now(result)));
-                        entry.insert(query);
-                        SetResult::Inserted
-                    } else {
-                        SetResult::Nothing
+                if let Some(hidden_at) = hidden_at.flatten() {
+                    let hidden = Instant::now() - hidden_at;
+                    for query in client.all_queries() {
+                        query.mark_stale_after_hidden(hidden);
                     }
                 }
-            }
+                None
+            });
         });
+    }
 
-        if let SetResult::Inserted = result {
-            self.notify.set(());
-        }
-
-        self
+    fn all_queries(&self) -> Vec<Box<dyn AnyQuery>> {
+        RefCell::borrow(&self.cache)
+            .values()
+            .flat_map(|cache| cache.queries())
+            .collect()
     }
 
     fn use_cache_option<K, V, F, R>(&self, func: F) -> Option<R>
     where
         K: 'static,
         V: 'static,
-        F: FnOnce(&HashMap<K, Query<K, V>>) -> Option<R>,
+        F: FnOnce(&KeyMap<K, Query<K, V>>) -> Option<R>,
         R: 'static,
     {
         let cache = RefCell::borrow(&self.cache);
         let type_key = (TypeId::of::<K>(), TypeId::of::<V>());
         let cache = cache.get(&type_key)?;
         let cache = cache.as_any().downcast_ref::<CacheEntry<K, V>>()?;
-        func(&cache.0)
+        let queries = RefCell::borrow(&cache.queries);
+        func(&queries)
     }
 
     fn use_cache_option_mut<K, V, F, R>(&self, func: F) -> Option<R>
     where
         K: 'static,
         V: 'static,
-        F: FnOnce(&mut HashMap<K, Query<K, V>>) -> Option<R>,
+        F: FnOnce(&mut KeyMap<K, Query<K, V>>) -> Option<R>,
         R: 'static,
     {
         let mut cache = self.cache.borrow_mut();
         let type_key = (TypeId::of::<K>(), TypeId::of::<V>());
         let cache = cache.get_mut(&type_key)?;
         let cache = cache.as_any_mut().downcast_mut::<CacheEntry<K, V>>()?;
-        func(&mut cache.0)
+        let mut queries = cache.queries.borrow_mut();
+        func(&mut queries)
+    }
+
+    fn use_cache<K, V, R>(
+        &self,
+        func: impl FnOnce((Owner, &mut KeyMap<K, Query<K, V>>)) -> R + 'static,
+    ) -> R
+    where
+        K: Clone + Eq + Hash + 'static,
+        V: Clone + 'static,
+    {
+        let owner = self.owner;
+        self.use_cache_entry(move |cache| func((owner, &mut cache.queries.borrow_mut())))
     }
 
-    fn use_cache<K, V, R>(
-        &self,
-        func: impl FnOnce((Owner, &mut HashMap<K, Query<K, V>>)) -> R + 'static,
-    ) -> R
+    fn use_cache_entry<K, V, R>(&self, func: impl FnOnce(&mut CacheEntry<K, V>) -> R) -> R
     where
-        K: Clone + 'static,
+        K: Clone + Eq + Hash + 'static,
         V: Clone + 'static,
     {
         let mut cache = self.cache.borrow_mut();
 
         let type_key = (TypeId::of::<K>(), TypeId::of::<V>());
 
-        let cache: &mut Box<dyn CacheEntryTrait> = match cache.entry(type_key) {
+        let entry: &mut Box<dyn CacheEntryTrait> = match cache.entry(type_key) {
             Entry::Occupied(o) => o.into_mut(),
             Entry::Vacant(v) => {
-                let wrapped: CacheEntry<K, V> = CacheEntry(HashMap::new());
+                let wrapped: CacheEntry<K, V> = CacheEntry::new();
                 v.insert(Box::new(wrapped))
             }
         };
 
-        let cache: &mut CacheEntry<K, V> = cache
+        // Can't fail, since the entry is keyed by the exact types it is downcast to.
+        let cache = entry
             .as_any_mut()
             .downcast_mut::<CacheEntry<K, V>>()
             .expect(
             "Error: Query Cache Type Mismatch. This should not happen. Please file a bug report.",
         );
+        func(cache)
+    }
 
-        func((self.owner, &mut cache.0))
+    // The typed queries of the current partition, created if there are none yet.
+    pub(crate) fn typed_queries<K, V>(&self) -> Queries<K, V>
+    where
+        K: Clone + Eq + Hash + 'static,
+        V: Clone + 'static,
+    {
+        self.use_cache_entry(|cache: &mut CacheEntry<K, V>| cache.queries.clone())
     }
 
-    fn get_or_create_query<K, V>(&self, key: K) -> (Query<K, V>, bool)
+    // Evicts the heaviest unused queries until the cache is within its weight limit.
+    pub(crate) fn enforce_max_cache_weight(&self) {
+        if let Some(max_weight) = self.max_cache_weight {
+            self.trim_cache(max_weight);
+        }
+    }
+
+    pub(crate) fn get_or_create_query<K, V>(&self, key: K) -> (Query<K, V>, bool)
     where
         K: Clone + Eq + Hash + 'static,
         V: Clone + 'static,
     {
+        // Query is owned by the caller's scope, and is disposed along with it.
+        if self.no_cache {
+            return (Query::new(key), true);
+        }
+
         let result = self.use_cache(move |(owner, cache)| {
             let entry = cache.entry(key.clone());
 
@@ -498,6 +1650,15 @@ impl QueryClient {
         result
     }
 
+    // The number of queries that are loading or fetching.
+    #[cfg_attr(not(feature = "test_utils"), allow(dead_code))]
+    pub(crate) fn fetching_queries(&self) -> usize {
+        self.all_queries()
+            .iter()
+            .filter(|query| query.is_fetching())
+            .count()
+    }
+
     pub(crate) fn get_query_signal<K, V>(
         &self,
         key: impl Fn() -> K + 'static,
@@ -506,33 +1667,133 @@ impl QueryClient {
         K: Hash + Eq + Clone + 'static,
         V: Clone + 'static,
     {
+        if self.no_cache {
+            return uncached_query_signal(key);
+        }
+
         let client = self.clone();
 
         // This memo is crucial to avoid crazy amounts of lookups.
+        // Looked up again when switching partitions.
         create_memo(move |_| {
+            client.partition.track();
             let key = key();
             client.get_or_create_query(key)
         })
         .into()
     }
 
-    pub(crate) fn evict_and_notify<K, V: 'static>(&self, key: &K) -> Option<Query<K, V>>
+    // Evicts the query from the partition it belongs to, which may not be the current one.
+    // The query of another partition with the same key is never evicted.
+    // The evicted query is released, so its signals are disposed once it's no longer observed.
+    pub(crate) fn evict_and_notify<K, V>(&self, query: &Query<K, V>) -> Option<Query<K, V>>
     where
         K: Hash + Eq + 'static,
         V: 'static,
     {
-        let result = self.use_cache_option_mut(move |cache| cache.remove(key));
+        let evict = |cache: &mut KeyMap<K, Query<K, V>>| match cache.get(&query.key) {
+            Some(cached) if cached.state == query.state => cache.remove(&query.key),
+            _ => None,
+        };
+        let result = self.use_cache_option_mut(evict).or_else(|| {
+            let type_key = (TypeId::of::<K>(), TypeId::of::<V>());
+            RefCell::borrow(&self.partitions)
+                .values()
+                .filter_map(|cache| cache.get(&type_key))
+                .filter_map(|cache| cache.as_any().downcast_ref::<CacheEntry<K, V>>())
+                .find_map(|cache| evict(&mut cache.queries.borrow_mut()))
+        });
 
-        if result.is_some() {
+        if let Some(ref query) = result {
+            query.release();
             self.notify.set(());
         }
         result
     }
 }
 
+// Without a cache, the caller owns its query. It's created once per key, outside of the memo so a rerun doesn't dispose it,
+// and released once the key changes or the caller's scope is disposed.
+fn uncached_query_signal<K, V>(key: impl Fn() -> K + 'static) -> Signal<(Query<K, V>, bool)>
+where
+    K: Hash + Eq + Clone + 'static,
+    V: Clone + 'static,
+{
+    let owner = Owner::current();
+    let current: Rc<RefCell<Option<Query<K, V>>>> = Rc::default();
+
+    on_cleanup({
+        let current = current.clone();
+        move || {
+            if let Some(query) = current.take() {
+                query.release();
+            }
+        }
+    });
+
+    create_memo(move |previous: Option<&(Query<K, V>, bool)>| {
+        let key = key();
+        if let Some(previous) = previous.filter(|(query, _)| query.key == key) {
+            return previous.clone();
+        }
+        let query = match owner {
+            Some(owner) => with_owner(owner, || Query::new(key)),
+            None => Query::new(key),
+        };
+        if let Some(replaced) = current.replace(Some(query.clone())) {
+            replaced.release();
+        }
+        (query, true)
+    })
+    .into()
+}
+
+/// Watches a query until it is dropped. Returned by [`QueryClient::watch_query`].
+#[must_use = "the query is only watched until the watcher is dropped"]
+pub struct QueryWatcher {
+    _disposer: Disposer,
+}
+
+impl QueryWatcher {
+    /// Stops watching the query. Same as dropping the watcher.
+    pub fn unsubscribe(self) {}
+}
+
+/// Returned by [`QueryClient::try_set_query_data`] when the query changed since the update was derived from it.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct QueryConflict {
+    /// The version the rejected update was based on.
+    pub expected: Option<u64>,
+    /// The version of the query currently in the cache.
+    pub actual: Option<u64>,
+}
+
+impl std::fmt::Display for QueryConflict {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match (self.expected, self.actual) {
+            (Some(expected), Some(actual)) => write!(
+                f,
+                "query is at version {actual}, but the update was based on version {expected}"
+            ),
+            (None, Some(actual)) => write!(
+                f,
+                "query was created at version {actual}, but the update expected no query"
+            ),
+            (Some(expected), None) => write!(
+                f,
+                "query at version {expected} was removed before the update"
+            ),
+            (None, None) => write!(f, "query conflict"),
+        }
+    }
+}
+
+impl std::error::Error for QueryConflict {}
+
 #[cfg(test)]
 mod tests {
     use super::*;
+    use std::cell::Cell;
 
     #[test]
     fn prefetch_loads_data() {
@@ -757,4 +2018,501 @@ mod tests {
             Some(QueryState::Invalid { .. })
         ));
     }
+
+    #[test]
+    fn on_query_success_is_called_after_fetch() {
+        let _ = create_runtime();
+
+        let successes = Rc::new(RefCell::new(Vec::new()));
+        provide_query_client_with(QueryClientBuilder::new().on_query_success({
+            let successes = successes.clone();
+            move |success| {
+                successes
+                    .borrow_mut()
+                    .push((success.key::<u32>().copied(), success.value_type()))
+            }
+        }));
+        let client = use_query_client();
+
+        client.prefetch_query(|| 0, |num: u32| async move { num.to_string() }, true);
+
+        assert_eq!(
+            vec![(Some(0), std::any::type_name::<String>())],
+            *RefCell::borrow(&successes)
+        );
+    }
+
+    #[test]
+    fn no_cache_never_shares_queries() {
+        let _ = create_runtime();
+
+        provide_query_client_with(QueryClientBuilder::new().no_cache());
+        let client = use_query_client();
+
+        let fetches = Rc::new(Cell::new(0));
+        let fetcher = {
+            let fetches = fetches.clone();
+            move |num: u32| {
+                fetches.set(fetches.get() + 1);
+                async move { num.to_string() }
+            }
+        };
+
+        client.prefetch_query(|| 0, fetcher.clone(), true);
+        client.prefetch_query(|| 0, fetcher, true);
+        client.set_query_data::<u32, String>(1, |_| Some("1".to_string()));
+
+        assert_eq!(2, fetches.get());
+        assert_eq!(0, client.size().get_untracked());
+    }
+
+    #[test]
+    fn no_cache_creates_one_query_per_key() {
+        let _ = create_runtime();
+
+        provide_query_client_with(QueryClientBuilder::new().no_cache());
+        let client = use_query_client();
+
+        let rerun = create_rw_signal(());
+        let id = create_rw_signal(0_u32);
+        let query = client.get_query_signal::<u32, u32>(move || {
+            rerun.track();
+            id.get()
+        });
+        let (first, new) = query.get_untracked();
+        assert!(new);
+
+        // The same key keeps the same query.
+        rerun.set(());
+        assert!(first.state == query.get_untracked().0.state);
+        assert!(first.state.try_get_untracked().is_some());
+
+        // A new key replaces it, and the unobserved old query is disposed.
+        id.set(1);
+        let (second, new) = query.get_untracked();
+        assert!(new);
+        assert_eq!(1, second.key);
+        assert!(first.state.try_get_untracked().is_none());
+        assert!(second.state.try_get_untracked().is_some());
+    }
+
+    #[test]
+    fn evicts_heaviest_unused_queries_over_max_weight() {
+        let _ = create_runtime();
+
+        provide_query_client_with(
+            QueryClientBuilder::new()
+                .max_cache_weight(10)
+                .weigher::<u32, String>(|value| value.len()),
+        );
+        let client = use_query_client();
+
+        client.set_query_data::<u32, String>(0, |_| Some("a".repeat(6)));
+        client.set_query_data::<u32, String>(1, |_| Some("b".repeat(2)));
+        client.set_query_data::<u32, String>(2, |_| Some("c".repeat(2)));
+        assert_eq!(3, client.size().get_untracked());
+
+        // Exceeds the limit, so the heaviest entry is evicted.
+        client.set_query_data::<u32, String>(3, |_| Some("d".repeat(2)));
+
+        assert_eq!(3, client.size().get_untracked());
+        assert_eq!(
+            None,
+            client.get_query_state::<u32, String>(|| 0).get_untracked()
+        );
+    }
+
+    #[test]
+    fn trims_cache_to_the_given_weight() {
+        let _ = create_runtime();
+
+        provide_query_client_with(
+            QueryClientBuilder::new().weigher::<u32, String>(|value| value.len()),
+        );
+        let client = use_query_client();
+
+        client.set_query_data::<u32, String>(0, |_| Some("a".repeat(6)));
+        client.set_query_data::<u32, String>(1, |_| Some("b".repeat(3)));
+        client.set_query_data::<u32, String>(2, |_| Some("c".to_string()));
+        // Data without a weigher isn't counted.
+        client.set_query_data::<u32, u32>(0, |_| Some(100));
+        assert_eq!(10, client.estimated_size());
+
+        // Without a max weight, nothing is evicted until asked to.
+        assert_eq!(4, client.size().get_untracked());
+        assert_eq!(4, client.trim_cache(5));
+        assert_eq!(None, client.cache::<u32, String>().get_data(0));
+        assert_eq!(3, client.size().get_untracked());
+
+        assert_eq!(0, client.trim_cache(0));
+        assert_eq!(1, client.size().get_untracked());
+    }
+
+    #[test]
+    fn never_evicts_observed_queries() {
+        let _ = create_runtime();
+
+        provide_query_client_with(
+            QueryClientBuilder::new()
+                .max_cache_weight(10)
+                .weigher::<u32, String>(|value| value.len()),
+        );
+        let client = use_query_client();
+
+        client.set_query_data::<u32, String>(0, |_| Some("a".repeat(6)));
+        let query = client.fetch_query(|| 0, |_: u32| async { "a".repeat(6) }, false);
+        create_isomorphic_effect(move |_| query.data.track());
+
+        client.set_query_data::<u32, String>(1, |_| Some("b".repeat(2)));
+        client.set_query_data::<u32, String>(2, |_| Some("c".repeat(2)));
+
+        // The observed query is the heaviest, so a lighter one is evicted instead.
+        client.set_query_data::<u32, String>(3, |_| Some("d".repeat(2)));
+
+        assert_eq!(3, client.size().get_untracked());
+        assert_eq!(
+            Some("a".repeat(6)),
+            client.cache::<u32, String>().get_data(0)
+        );
+        assert_eq!(Some("a".repeat(6)), query.data.get_untracked());
+    }
+
+    #[test]
+    fn iter_entries_snapshots_cached_queries() {
+        let _ = create_runtime();
+
+        provide_query_client();
+        let client = use_query_client();
+
+        assert!(client.iter_entries::<u32, String>().is_empty());
+
+        client.set_query_data::<u32, String>(0, |_| Some("a".to_string()));
+        client.set_query_data::<u32, String>(1, |_| Some("b".to_string()));
+        client.set_query_data::<u32, u32>(2, |_| Some(2));
+
+        let mut entries: Vec<(u32, Option<String>)> = client
+            .iter_entries::<u32, String>()
+            .into_iter()
+            .map(|(key, state)| (key, state.data().cloned()))
+            .collect();
+        entries.sort();
+
+        assert_eq!(
+            vec![(0, Some("a".to_string())), (1, Some("b".to_string()))],
+            entries
+        );
+    }
+
+    #[test]
+    #[cfg(debug_assertions)]
+    fn warns_on_nested_query_client() {
+        let _ = create_runtime();
+
+        let logger = crate::query_logger::RecordingLogger::default();
+
+        provide_query_client_with(QueryClientBuilder::new().logger(logger.clone()));
+        assert!(logger.warnings().is_empty());
+
+        provide_query_client_with(QueryClientBuilder::new().logger(logger.clone()));
+
+        assert_eq!(1, logger.warnings().len());
+    }
+
+    #[test]
+    fn try_set_query_data_rejects_stale_updates() {
+        let _ = create_runtime();
+
+        provide_query_client();
+        let client = use_query_client();
+
+        assert!(client
+            .try_set_query_data::<u32, u32>(0, None, |_| Some(1))
+            .is_ok());
+
+        let based_on = client.get_query_version::<u32, u32>(0);
+        assert!(based_on.is_some());
+
+        // Concurrent write, within the same millisecond.
+        client.set_query_data::<u32, u32>(0, |_| Some(2));
+
+        let conflict = client
+            .try_set_query_data::<u32, u32>(0, based_on, |_| Some(3))
+            .err()
+            .expect("conflict");
+        assert_eq!(based_on, conflict.expected);
+        assert_ne!(conflict.expected, conflict.actual);
+
+        assert!(client
+            .try_set_query_data::<u32, u32>(0, conflict.actual, |_| Some(4))
+            .is_ok());
+        assert_eq!(
+            Some(4),
+            client.iter_entries::<u32, u32>()[0].1.data().copied()
+        );
+
+        // Fetches are writes too.
+        let based_on = client.get_query_version::<u32, u32>(0);
+        client.refetch_query_with::<u32, u32, _>(0, |_| async { 5 });
+        assert!(client
+            .try_set_query_data::<u32, u32>(0, based_on, |_| Some(6))
+            .is_err());
+    }
+
+    #[test]
+    fn refetch_query_with_uses_override_fetcher() {
+        let _ = create_runtime();
+
+        provide_query_client();
+        let client = use_query_client();
+
+        assert!(!client.refetch_query_with::<u32, String, _>(0, |_| async { "b".to_string() }));
+
+        client.set_query_data::<u32, String>(0, |_| Some("a".to_string()));
+        assert!(client
+            .refetch_query_with::<u32, String, _>(0, |id| async move { format!("forced {id}") }));
+
+        assert_eq!(
+            Some("forced 0".to_string()),
+            client.iter_entries::<u32, String>()[0].1.data().cloned()
+        );
+    }
+
+    #[test]
+    fn query_meta_is_stored_per_entry() {
+        let _ = create_runtime();
+
+        provide_query_client();
+        let client = use_query_client();
+
+        assert!(!client.set_query_meta::<u32, u32>(0, QueryMeta::new("server")));
+
+        client.set_query_data::<u32, u32>(0, |_| Some(0));
+        client.set_query_data::<u32, u32>(1, |_| Some(1));
+        assert!(client.set_query_meta::<u32, u32>(0, QueryMeta::new("server")));
+
+        let source = |key: u32| {
+            client
+                .get_query_meta::<u32, u32>(key)
+                .and_then(|meta| meta.get::<&str>().copied())
+        };
+        assert_eq!(Some("server"), source(0));
+        assert_eq!(None, source(1));
+    }
+
+    #[test]
+    fn seeding_keeps_newer_data() {
+        let _ = create_runtime();
+
+        provide_query_client();
+        let client = use_query_client();
+
+        let loaded = QueryData::now(1_u32);
+        assert!(client.seed_query_data(0_u32, loaded.clone()));
+        assert!(!client.seed_query_data(0_u32, loaded.clone()));
+
+        client.set_query_data::<u32, u32>(1, |_| Some(2));
+        assert!(!client.seed_query_data(1_u32, loaded.clone()));
+
+        let state = client.clone().get_query_state::<u32, u32>(|| 0);
+        assert_eq!(Some(QueryState::Loaded(loaded)), state.get_untracked());
+    }
+
+    #[test]
+    fn edits_list_data() {
+        let _ = create_runtime();
+
+        provide_query_client();
+        let client = use_query_client();
+
+        // Lists without data aren't created.
+        client.append_query_item::<u32, u32>(0, 1);
+        assert_eq!(0, client.size().get_untracked());
+
+        let result = client.fetch_query(|| 0_u32, |_| async { vec![1_u32, 2, 3] }, true);
+        client.append_query_item::<u32, u32>(0, 4);
+        result.append_item(5);
+        assert_eq!(2, result.update_where(|n| n % 2 == 0, |n| *n *= 10));
+        assert_eq!(0, client.remove_query_items::<u32, u32>(0, |n| *n == 2));
+        assert_eq!(2, result.remove_where(|n| *n >= 20));
+
+        assert_eq!(Some(vec![1, 3, 5]), result.data.get_untracked());
+    }
+
+    #[test]
+    fn client_can_be_used_reentrantly() {
+        let _ = create_runtime();
+
+        provide_query_client();
+        let client = use_query_client();
+
+        client.set_query_data::<u32, u32>(0, |_| Some(1));
+        client.set_query_data::<u32, u32>(1, {
+            let client = client.clone();
+            move |_| {
+                client.set_query_data::<u32, u32>(0, |n| n.map(|n| n + 1));
+                Some(
+                    client
+                        .clone()
+                        .get_query_state::<u32, u32>(|| 0)
+                        .get_untracked()?
+                        .data()?
+                        * 10,
+                )
+            }
+        });
+
+        // Observers of invalidated queries can use the client synchronously.
+        let (query, _) = client.get_or_create_query::<u32, u32>(0);
+        create_isomorphic_effect({
+            let client = client.clone();
+            move |_| {
+                if let QueryState::Invalid(_) = query.state.get() {
+                    client.set_query_data::<u32, u32>(2, |_| Some(3));
+                    client.invalidate_query::<u32, u32>(1);
+                }
+            }
+        });
+        client.invalidate_all_queries();
+
+        let state = |key| {
+            client
+                .clone()
+                .get_query_state::<u32, u32>(move || key)
+                .get_untracked()
+        };
+        assert_eq!(Some(20), state(1).and_then(|s| s.data().cloned()));
+        assert!(matches!(state(2), Some(QueryState::Loaded(_))));
+    }
+
+    #[test]
+    fn watches_query_until_unsubscribed() {
+        let _ = create_runtime();
+
+        provide_query_client();
+        let client = use_query_client();
+
+        let seen = Rc::new(RefCell::new(Vec::new()));
+        let watcher = client.watch_query::<u32, u32>(&0, {
+            let seen = seen.clone();
+            move |state| seen.borrow_mut().push(state.data().cloned())
+        });
+
+        // A single update, without the transient Created state.
+        client.set_query_data::<u32, u32>(0, |_| Some(1));
+        assert_eq!(vec![Some(1)], *RefCell::borrow(&seen));
+
+        // Other keys, and lookups that create nothing, aren't reported.
+        client.set_query_data::<u32, u32>(1, |_| Some(1));
+        let _ = client.get_or_create_query::<u32, u32>(0);
+        assert_eq!(vec![Some(1)], *RefCell::borrow(&seen));
+
+        client.set_query_data::<u32, u32>(0, |_| Some(2));
+        watcher.unsubscribe();
+        client.set_query_data::<u32, u32>(0, |_| Some(3));
+
+        assert_eq!(vec![Some(1), Some(2)], *RefCell::borrow(&seen));
+    }
+
+    #[test]
+    fn freshness_overrides_survive_new_observers_until_next_fetch() {
+        let _ = create_runtime();
+
+        provide_query_client();
+        let client = use_query_client();
+
+        assert!(!client.set_query_stale_time::<u32, u32>(0, None));
+
+        let (query, _) = client.get_or_create_query::<u32, u32>(0);
+        client.set_query_data::<u32, u32>(0, |_| Some(1));
+        let hour = Duration::from_secs(60 * 60);
+        assert!(client.set_query_stale_time::<u32, u32>(0, Some(hour)));
+        assert!(client.set_query_cache_time::<u32, u32>(0, None));
+
+        let id = query.register_options(&QueryOptions::stale_time(Duration::from_secs(1)));
+        let options = client.get_query_options::<u32, u32>(0).unwrap();
+        assert_eq!(Some(hour), options.stale_time);
+        assert_eq!(None, options.cache_time);
+
+        client.refetch_query_with::<u32, u32, _>(0, |_| async { 2 });
+        let options = client.get_query_options::<u32, u32>(0).unwrap();
+        assert_eq!(Some(Duration::from_secs(1)), options.stale_time);
+        query.unregister_options(id);
+    }
+
+    #[test]
+    fn partitions_have_separate_caches() {
+        let _ = create_runtime();
+
+        provide_query_client();
+        let client = use_query_client();
+
+        let query = client.get_query_signal::<u32, u32>(|| 0);
+        let data = |client: &QueryClient| {
+            client
+                .iter_entries::<u32, u32>()
+                .first()
+                .and_then(|(_, state)| state.data().copied())
+        };
+        client.set_query_data::<u32, u32>(0, |_| Some(1));
+        let default_query = query.get_untracked().0;
+
+        client.set_partition("alice");
+        assert_eq!(
+            Some("alice".to_string()),
+            client.partition().get_untracked()
+        );
+        assert_eq!(None, data(&client));
+        assert!(query.get_untracked().0.state != default_query.state);
+        client.set_query_data::<u32, u32>(0, |_| Some(2));
+
+        client.clear_partition();
+        assert_eq!(Some(1), data(&client));
+        assert!(query.get_untracked().0.state == default_query.state);
+
+        client.set_partition("alice");
+        let alice_query = query.get_untracked().0;
+        let alice_cache = client.cache::<u32, u32>();
+        assert!(!client.remove_partition("alice"));
+        client.clear_partition();
+        assert!(client.remove_partition("alice"));
+        client.set_partition("alice");
+        assert_eq!(None, data(&client));
+
+        // Queries in use and cache handles switch to the new cache of the partition.
+        assert!(query.get_untracked().0.state != alice_query.state);
+        assert_eq!(None, alice_cache.get_data(0));
+        client.set_query_data::<u32, u32>(0, |_| Some(3));
+        assert_eq!(Some(3), alice_cache.get_data(0));
+        assert_eq!(
+            Some(3),
+            query
+                .get_untracked()
+                .0
+                .state
+                .with_untracked(|s| s.data().copied())
+        );
+    }
+
+    #[test]
+    fn evicts_queries_of_the_partition_they_belong_to() {
+        let _ = create_runtime();
+
+        provide_query_client();
+        let client = use_query_client();
+        let data = |client: &QueryClient| client.cache::<u32, u32>().get_data(0);
+
+        client.set_query_data::<u32, u32>(0, |_| Some(1));
+        let (default_query, _) = client.get_or_create_query::<u32, u32>(0);
+
+        // The partition switches before the cache time of the query expires.
+        client.set_partition("alice");
+        client.set_query_data::<u32, u32>(0, |_| Some(2));
+
+        // Once it does, the query is evicted from its own partition, not the current one.
+        assert!(client.evict_and_notify(&default_query).is_some());
+        assert_eq!(Some(2), data(&client));
+        client.clear_partition();
+        assert_eq!(None, data(&client));
+        assert!(client.evict_and_notify(&default_query).is_none());
+    }
 }
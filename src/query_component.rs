@@ -0,0 +1,95 @@
+use leptos::*;
+use std::{future::Future, hash::Hash};
+
+use crate::{use_query, QueryOptions};
+
+/// Renders the data of a query, for quick cases where defining a custom hook is overkill.
+///
+/// Uses [`use_query`](crate::use_query()) internally. The children receive the query's data,
+/// and are rendered like [`QueryResult::render_with_fallback`](crate::QueryResult::render_with_fallback),
+/// inside a [`Transition`] which shows the fallback until the data is first loaded.
+///
+/// Example:
+/// ```no_run
+/// use leptos::*;
+/// use leptos_query::*;
+/// # #[derive(Clone, serde::Deserialize, serde::Serialize)]
+/// # struct Monkey { name: String }
+/// # async fn get_monkey(id: u32) -> Monkey { todo!() }
+///
+/// #[component]
+/// fn MonkeyName(id: u32) -> impl IntoView {
+///     view! {
+///         <Query key=move || id fetcher=get_monkey let:monkey>
+///             <h2>{monkey.name}</h2>
+///         </Query>
+///     }
+/// }
+/// ```
+#[component]
+pub fn Query<K, V, Fu, KeyFn, Fetcher, Children, IV>(
+    /// The key of the query.
+    key: KeyFn,
+    /// The fetcher of the query.
+    fetcher: Fetcher,
+    /// The options of the query.
+    #[prop(optional, into)]
+    options: Option<QueryOptions<V>>,
+    /// Shown until the data is first loaded.
+    #[prop(optional, into)]
+    fallback: ViewFn,
+    /// Renders the query's data.
+    children: Children,
+) -> impl IntoView
+where
+    K: Hash + Eq + Clone + 'static,
+    V: Clone + Serializable + 'static,
+    Fu: Future<Output = V> + 'static,
+    KeyFn: Fn() -> K + 'static,
+    Fetcher: Fn(K) -> Fu + 'static,
+    Children: Fn(V) -> IV + 'static,
+    IV: IntoView,
+{
+    use_query(key, fetcher, options.unwrap_or_default()).render_with_fallback(fallback, children)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{provide_query_client, use_query_client, SsrPolicy};
+
+    #[test]
+    fn renders_the_data_of_the_query() {
+        let html = leptos::ssr::render_to_string(|| {
+            provide_query_client();
+            use_query_client().set_query_data::<u32, String>(1, |_| Some("George".into()));
+
+            // Not fetched where the code runs, so it has no data.
+            let not_here = QueryOptions::builder()
+                .ssr(if cfg!(feature = "ssr") {
+                    SsrPolicy::ClientOnly
+                } else {
+                    SsrPolicy::ServerOnly
+                })
+                .build();
+            view! {
+                <Query key=|| 1_u32 fetcher=|_| async { "George".to_string() } let:name>
+                    <h2>{name}</h2>
+                </Query>
+                <Query
+                    key=|| 2_u32
+                    fetcher=|_| async { "Curious George".to_string() }
+                    options=not_here
+                    fallback=|| "Finding the monkey"
+                    let:name
+                >
+                    <h2>{name}</h2>
+                </Query>
+            }
+        });
+
+        assert!(html.contains(">George</h2>"));
+        assert!(!html.contains("Curious George"));
+        assert!(html.contains("Finding the monkey"));
+    }
+}
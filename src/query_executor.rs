@@ -1,21 +1,27 @@
 use leptos::*;
 use std::{
+    any::Any,
     cell::{Cell, RefCell},
-    collections::HashMap,
-    future::Future,
+    future::{poll_fn, Future},
     hash::Hash,
     rc::Rc,
+    task::Poll,
 };
 
 use crate::{
+    diagnostics::{check_overfetch, check_unread_refetch},
     query::Query,
+    scheduler::TimerHandle,
     use_query_client,
-    util::{maybe_time_until_stale, time_until_stale, use_timeout},
-    QueryData, QueryState,
+    util::{
+        maybe_time_until_stale, next_aligned, time_until, time_until_stale, use_timeout, KeyMap,
+    },
+    FetchContext, Partial, Previous, QueryClient, QueryData, QueryState, QuerySuccess,
+    INFINITE_TIME,
 };
 
 thread_local! {
-    static SUPPRESS_QUERY_LOAD: Cell<bool> = Cell::new(false);
+    static SUPPRESS_QUERY_LOAD: Cell<bool> = const { Cell::new(false) };
 }
 
 /// Disable or enable query loading.
@@ -23,7 +29,7 @@ thread_local! {
 /// Useful for disabling query loads during App introspection, such as SSR Router integrations for Actix/Axum.
 ///
 /// Example for `generate_route_list`
-/// ```
+/// ```ignore
 /// // Disable query loading.
 /// leptos_query::suppress_query_load(true);
 /// // Introspect App Routes.
@@ -31,7 +37,6 @@ thread_local! {
 /// // Enable query loading.
 /// leptos_query::suppress_query_load(false);
 /// ```
-
 pub fn suppress_query_load(suppress: bool) {
     SUPPRESS_QUERY_LOAD.with(|w| w.set(suppress));
 }
@@ -46,50 +51,247 @@ where
     V: Clone + 'static,
     Fu: Future<Output = V> + 'static,
 {
+    let client = use_query_client();
+    let online = client.online_manager.online();
     let fetcher = Rc::new(fetcher);
-    move || {
+    // The query this observer paused while offline, to resume once back online.
+    let waiting_on: Rc<RefCell<Option<Query<K, V>>>> = Rc::default();
+    let pausing = waiting_on.clone();
+    let executor = move || {
         let fetcher = fetcher.clone();
+        let client = client.clone();
+        let pausing = pausing.clone();
+        // Taken before spawning, so they only apply to the fetch they were set for, even if it doesn't happen.
+        let (quiet, requested) = query.with_untracked(|query| {
+            (
+                query.quiet_next_fetch.replace(false),
+                query.refetch_requested.replace(false),
+            )
+        });
         SUPPRESS_QUERY_LOAD.with(|supressed| {
             if !supressed.get() {
                 spawn_local(async move {
                     let query = query.get_untracked();
                     let data_state = query.state.get_untracked();
                     match data_state {
+                        // Already fetching, or prevented from fetching.
+                        _ if query.fetching_quietly.get_untracked() => (),
                         QueryState::Fetching(_) | QueryState::Loading => (),
-                        // First load.
-                        QueryState::Created => {
-                            query.state.set(QueryState::Loading);
-                            let data = fetcher(query.key.clone()).await;
-                            let updated_at = crate::Instant::now();
-                            let data = QueryData { data, updated_at };
-                            query.state.set(QueryState::Loaded(data));
+                        // Just fetched, by an effect that ran moments before this one.
+                        QueryState::Loaded(ref data)
+                            if !requested && query.fetched_moments_ago(data) =>
+                        {
+                            client
+                                .deduplicated_fetches
+                                .set(client.deduplicated_fetches.get() + 1);
+                        }
+                        // Wait for the network to come back before fetching.
+                        _ if !online.get_untracked() => {
+                            query.paused.set(true);
+                            *pausing.borrow_mut() = Some(query);
+                        }
+                        // First load. Idle queries are only fetched by observers that are allowed to.
+                        QueryState::Created | QueryState::Idle => {
+                            query.set_state(QueryState::Loading);
+                            fetch(&client, &query, move |key| fetcher(key)).await;
+                        }
+                        // Subsequent loads, keeping the current state while fetching.
+                        QueryState::Loaded(_) | QueryState::Invalid(_) if quiet => {
+                            query.fetching_quietly.set(true);
+                            fetch(&client, &query, move |key| fetcher(key)).await;
                         }
                         // Subsequent loads.
                         QueryState::Loaded(data) | QueryState::Invalid(data) => {
-                            query.state.set(QueryState::Fetching(data));
-                            let data = fetcher(query.key.clone()).await;
-                            let updated_at = crate::Instant::now();
-                            let data = QueryData { data, updated_at };
-                            query.state.set(QueryState::Loaded(data));
+                            query.set_state(QueryState::Fetching(data));
+                            fetch(&client, &query, move |key| fetcher(key)).await;
                         }
                     }
                 })
             }
         })
+    };
+
+    // A query this observer switched away from while offline is no longer waiting for it.
+    create_isomorphic_effect({
+        let waiting_on = waiting_on.clone();
+        move |_| {
+            let current = query.get();
+            let switched = waiting_on
+                .borrow()
+                .as_ref()
+                .is_some_and(|paused| *paused != current);
+            if switched {
+                if let Some(paused) = waiting_on.take() {
+                    let _ = paused.paused.try_set(false);
+                }
+            }
+        }
+    });
+
+    // Resume paused fetches once back online.
+    create_effect({
+        let executor = executor.clone();
+        move |_| {
+            if online.get() {
+                if let Some(paused) = waiting_on.take() {
+                    let _ = paused.paused.try_set(false);
+                    executor();
+                }
+            }
+        }
+    });
+
+    executor
+}
+
+// Execute the fetcher within the query's FetchContext, and store the result.
+pub(crate) async fn fetch<K, V, Fu>(
+    client: &QueryClient,
+    query: &Query<K, V>,
+    fetcher: impl FnOnce(K) -> Fu + 'static,
+) where
+    K: Clone + 'static,
+    V: Clone + 'static,
+    Fu: Future<Output = V> + 'static,
+{
+    let started_at = crate::Instant::now();
+    check_overfetch(client, query, started_at);
+    check_unread_refetch(client, query, started_at);
+    let generation = query.fetch_generation.get();
+    let state = query.state;
+    // Partial data keeps the update time of the data it replaces, so it's never fresher than that.
+    let previous_updated_at = state.with_untracked(|state| state.updated_at());
+    let partial = {
+        let fetch_generation = query.fetch_generation.clone();
+        let version = query.version.clone();
+        let is_partial = query.partial.clone();
+        Partial(Rc::new(move |data: Box<dyn Any>| {
+            if fetch_generation.get() != generation {
+                return false;
+            }
+            let Ok(data) = data.downcast::<V>() else {
+                return false;
+            };
+            let fetching = state
+                .try_with_untracked(|state| {
+                    matches!(state, QueryState::Loading | QueryState::Fetching(_))
+                })
+                .unwrap_or(false);
+            if fetching {
+                version.set(version.get() + 1);
+                is_partial.set(true);
+                state.set(QueryState::Fetching(QueryData {
+                    data: *data,
+                    updated_at: previous_updated_at.unwrap_or(started_at),
+                }));
+            }
+            fetching
+        }))
+    };
+    let context = FetchContext {
+        meta: query.meta.get_untracked(),
+        freshness: Default::default(),
+        previous: Previous {
+            updated_at: previous_updated_at,
+            data: Some(Rc::new(move || {
+                state
+                    .try_with_untracked(|state| {
+                        let data = state.data()?.clone();
+                        Some(Box::new(data) as Box<dyn Any>)
+                    })
+                    .flatten()
+            })),
+        },
+        partial: Some(partial),
+        progress: Some(query.progress),
+    };
+    let freshness = context.freshness.clone();
+    let key = query.key.clone();
+    let mut fetch = Box::pin(context.scope(async move { fetcher(key).await }));
+
+    // Dropping the fetcher's future aborts it once the fetch is cancelled.
+    let data = poll_fn(|cx| {
+        if query.fetch_generation.get() != generation {
+            return Poll::Ready(None);
+        }
+        *query.fetch_waker.borrow_mut() = Some(cx.waker().clone());
+        fetch.as_mut().poll(cx).map(Some)
+    })
+    .await;
+    query.fetch_waker.take();
+    // Cancelled, or the query was disposed. Cancelling resets the progress itself.
+    let Some(data) = data else {
+        return;
+    };
+    if query.progress.get_untracked().is_some() {
+        query.progress.set(None);
+    }
+    let updated_at = crate::Instant::now();
+
+    // Apply freshness set by the fetcher before the new data is observed.
+    // Replaces the overrides of the previous data, if the fetcher sets none.
+    query.set_freshness(*freshness.borrow());
+
+    let data = QueryData { data, updated_at };
+    query.partial.set(false);
+    query.fetched_at.set(Some(updated_at));
+    query.set_state(QueryState::Loaded(data));
+    if query.fetching_quietly.get_untracked() {
+        query.fetching_quietly.set(false);
+    }
+    if query.invalidated_while_fetching.replace(false) {
+        query.mark_invalid();
     }
+    client.enforce_max_cache_weight();
+
+    if let Some(on_success) = client.on_query_success.as_ref() {
+        on_success(&QuerySuccess {
+            key: Rc::new(query.key.clone()),
+            key_type: std::any::type_name::<K>(),
+            value_type: std::any::type_name::<V>(),
+            started_at,
+            updated_at,
+        });
+    }
+
+    // Callbacks of the observers, once per completed fetch.
+    let (on_success, on_settled) = query.fetch_callbacks();
+    if on_success.is_empty() && on_settled.is_empty() {
+        return;
+    }
+    let Some(data) = state.with_untracked(|state| state.data().cloned()) else {
+        return;
+    };
+    on_success
+        .iter()
+        .chain(on_settled.iter())
+        .for_each(|callback| callback(&data));
 }
 
 // Start synchronization effects.
+/// How an observer refetches on its own.
+#[derive(Clone, Default)]
+pub(crate) struct RefetchOptions {
+    /// Refetch without toggling the state to Fetching, see `QueryOptions::refetch_interval_quietly`.
+    pub(crate) quietly: MaybeSignal<bool>,
+    /// Refetch at wall clock multiples of the interval, see `QueryOptions::refetch_interval_aligned`.
+    pub(crate) aligned: MaybeSignal<bool>,
+    /// Refetch stale data once the app regains focus, see `QueryOptions::refetch_on_focus`.
+    pub(crate) on_focus: MaybeSignal<bool>,
+}
+
 pub(crate) fn synchronize_state<K, V>(
     query: Signal<Query<K, V>>,
     executor: impl Fn() + Clone + 'static,
+    refetch_options: RefetchOptions,
 ) where
     K: Hash + Eq + Clone + 'static,
     V: Clone,
 {
     ensure_not_stale(query, executor.clone());
     ensure_not_invalid(query, executor.clone());
-    sync_refetch(query, executor.clone());
+    refetch_on_focus(query, executor.clone(), refetch_options.on_focus);
+    sync_refetch(query, executor.clone(), refetch_options);
     let query = Signal::derive(move || Some(query.get()));
     synchronize_observer(query);
 }
@@ -123,41 +325,116 @@ fn ensure_not_stale<K: Clone, V: Clone>(
     });
 }
 
+/// Refetch stale data when the app regains focus, if the observer asks for it.
+fn refetch_on_focus<K: Clone, V: Clone>(
+    query: Signal<Query<K, V>>,
+    executor: impl Fn() + 'static,
+    on_focus: MaybeSignal<bool>,
+) {
+    let focused = use_query_client().focus_manager.focused();
+    create_isomorphic_effect(move |was_focused: Option<bool>| {
+        let focused = focused.get();
+        if was_focused == Some(false) && focused && on_focus.get_untracked() {
+            let query = query.get_untracked();
+            if let (Some(updated_at), Some(stale_time)) = (
+                query.state.get_untracked().updated_at(),
+                query.stale_time.get_untracked(),
+            ) {
+                if time_until_stale(updated_at, stale_time).is_zero() {
+                    executor();
+                }
+            }
+        }
+        focused
+    });
+}
+
 /// Refetch data once marked as invalid.
+/// With an invalidation debounce, the refetch waits for the burst of invalidations to settle.
 fn ensure_not_invalid<K: Clone, V: Clone>(
     state: Signal<Query<K, V>>,
-    executor: impl Fn() + 'static,
+    executor: impl Fn() + Clone + 'static,
 ) {
-    create_isomorphic_effect(move |_| {
-        let state = state.get();
-        // Refetch query if Invalid.
-        if let QueryState::Invalid(_) = state.state.get() {
-            executor()
+    let client = use_query_client();
+    match client.invalidation_debounce {
+        None => {
+            create_isomorphic_effect(move |_| {
+                let state = state.get();
+                // Refetch query if Invalid.
+                if let QueryState::Invalid(_) = state.state.get() {
+                    executor()
+                }
+            });
         }
-    });
+        Some(debounce) => {
+            // Isomorphic like the immediate refetch, rather than through `use_timeout`.
+            let scheduler = client.scheduler;
+            let pending: Rc<RefCell<Option<TimerHandle>>> = Rc::default();
+            on_cleanup({
+                let pending = pending.clone();
+                move || {
+                    if let Some(handle) = pending.take() {
+                        handle.clear();
+                    }
+                }
+            });
+            create_isomorphic_effect(move |_| {
+                let state = state.get();
+                if let Some(handle) = pending.take() {
+                    handle.clear();
+                }
+                if let QueryState::Invalid(_) = state.state.get() {
+                    *pending.borrow_mut() = Some(scheduler.schedule(executor.clone(), debounce));
+                }
+            });
+        }
+    }
 }
 
 /// Effect for refetching query on interval, if present.
-fn sync_refetch<K, V>(query: Signal<Query<K, V>>, executor: impl Fn() + Clone + 'static)
-where
+/// Paused while the app is hidden, if the client marks queries stale on focus instead.
+/// Aligned intervals refetch at the next wall clock multiple of the interval after the data was updated.
+fn sync_refetch<K, V>(
+    query: Signal<Query<K, V>>,
+    executor: impl Fn() + Clone + 'static,
+    RefetchOptions {
+        quietly, aligned, ..
+    }: RefetchOptions,
+) where
     K: Clone + 'static,
     V: Clone + 'static,
 {
+    let client = use_query_client();
+    let scheduler = client.scheduler;
+    let paused_while_hidden = client
+        .mark_stale_on_focus
+        .then(|| client.focus_manager.focused());
     let _ = use_timeout(move || {
+        if paused_while_hidden.is_some_and(|focused| !focused.get()) {
+            return None;
+        }
         let query = query.get();
         let updated_at = query.state.get().updated_at();
         let refetch_interval = query.refetch_interval.get();
         match (updated_at, refetch_interval) {
-            (Some(updated_at), Some(refetch_interval)) => {
+            // An interval that never elapses needs no timer.
+            (Some(updated_at), Some(refetch_interval)) if refetch_interval != INFINITE_TIME => {
                 let executor = executor.clone();
-                let timeout = time_until_stale(updated_at, refetch_interval);
-                set_timeout_with_handle(
+                let quiet_next_fetch = query.quiet_next_fetch.clone();
+                let timeout = if aligned.get() {
+                    time_until(next_aligned(updated_at, refetch_interval))
+                } else {
+                    time_until_stale(updated_at, refetch_interval)
+                };
+                Some(scheduler.schedule(
                     move || {
+                        quiet_next_fetch.set(quietly.get_untracked());
                         executor();
+                        // Cleared even if the executor didn't run, such as where the query isn't fetched.
+                        quiet_next_fetch.set(false);
                     },
                     timeout,
-                )
-                .ok()
+                ))
             }
             _ => None,
         }
@@ -165,34 +442,29 @@ where
 }
 
 // Ensure that observers are kept track of.
+// Evicted queries are disposed once their last observer is gone, see `Query::release`.
 fn sync_observers<K: Clone, V: Clone>(query: Signal<Option<Query<K, V>>>) {
-    type Observer = Rc<Cell<usize>>;
-    let last_observer: Rc<Cell<Option<Observer>>> = Rc::new(Cell::new(None));
+    let last_observed: Rc<RefCell<Option<Query<K, V>>>> = Rc::new(RefCell::new(None));
 
     on_cleanup({
-        let last_observer = last_observer.clone();
+        let last_observed = last_observed.clone();
         move || {
-            if let Some(observer) = last_observer.take() {
-                observer.set(observer.get() - 1);
+            if let Some(query) = last_observed.take() {
+                query.remove_observer();
             }
         }
     });
 
     // Ensure that observers are kept track of.
-    create_isomorphic_effect(move |observers: Option<Option<Rc<Cell<usize>>>>| {
+    create_isomorphic_effect(move |_| {
         // Decrement previous observers.
-        if let Some(observers) = observers.flatten() {
-            last_observer.set(None);
-            observers.set(observers.get() - 1);
+        if let Some(query) = last_observed.take() {
+            query.remove_observer();
         }
         // Deal with latest observers.
         if let Some(query) = query.get() {
-            let observers = query.observers;
-            last_observer.set(Some(observers.clone()));
-            observers.set(observers.get() + 1);
-            Some(observers)
-        } else {
-            None
+            query.observers.set(query.observers.get() + 1);
+            *last_observed.borrow_mut() = Some(query);
         }
     });
 }
@@ -203,7 +475,7 @@ where
     K: Clone + Hash + Eq + 'static,
     V: Clone + 'static,
 {
-    let owner = use_query_client().owner;
+    let client = use_query_client();
 
     let child_disposed = Rc::new(Cell::new(false));
     on_cleanup({
@@ -211,91 +483,507 @@ where
         move || child_disposed.set(true)
     });
 
-    // Keep track of existing timeouts for keys.
-    let timeout_map = Rc::new(RefCell::new(HashMap::<K, Box<dyn Fn()>>::new()));
+    // The key this observer currently holds.
+    let held = Rc::new(RefCell::new(None::<K>));
 
-    // Functions that should be run on scope cleanup.
-    let cleanup_map = Rc::new(RefCell::new(HashMap::<K, Box<dyn FnOnce()>>::new()));
+    // Cache time timeouts that haven't run yet, by key.
+    // They're not tied to this scope, so queries are still evicted after it's disposed.
+    let timeout_map = Rc::new(RefCell::new(KeyMap::<K, TimerHandle>::default()));
+
+    // Evictions of keys whose cache time ran out while they were held, run once they no longer are.
+    let cleanup_map = Rc::new(RefCell::new(KeyMap::<K, Box<dyn FnOnce()>>::default()));
     on_cleanup({
         let key_to_on_cleanup = cleanup_map.clone();
         move || {
-            let mut map = key_to_on_cleanup.borrow_mut();
-            map.drain().for_each(|(_, cleanup)| cleanup());
+            let cleanups: Vec<_> = key_to_on_cleanup.borrow_mut().drain().collect();
+            cleanups.into_iter().for_each(|(_, cleanup)| cleanup());
         }
     });
 
-    // Create outer effect with child scope, and create timeout on root scope.
-    create_effect(move |_| {
-        // These signals can't go inside use_timeout because they will be disposed of before the timeout executes.
-        if let Some(query) = query.get() {
-            let updated_at = query.state.get().updated_at();
-            let cache_time = query.cache_time.get();
+    create_isomorphic_effect(move |_| {
+        let query = query.get();
 
-            // Remove key from cleanup map.
-            {
-                let mut cleanup_map = cleanup_map.borrow_mut();
-                cleanup_map.remove(&query.key);
-                drop(cleanup_map);
+        // Once the previous key is no longer held, it's evicted if its cache time already ran out.
+        let key = query.as_ref().map(|query| query.key.clone());
+        let released = held
+            .replace(key.clone())
+            .filter(|prev| Some(prev) != key.as_ref());
+        if let Some(released) = released {
+            let cleanup = cleanup_map.borrow_mut().remove(&released);
+            if let Some(cleanup) = cleanup {
+                cleanup();
             }
+        }
 
-            // Clear previous timeout for key.
-            let mut timeout_map = timeout_map.borrow_mut();
-            if let Some(clear) = timeout_map.remove(&query.key) {
-                clear()
-            }
+        let Some(query) = query else {
+            return;
+        };
+        let updated_at = query.state.get().updated_at();
+        let cache_time = query.cache_time.get();
 
-            let child_disposed = child_disposed.clone();
-            let cleanup_map = cleanup_map.clone();
+        // Remove key from cleanup map.
+        cleanup_map.borrow_mut().remove(&query.key);
 
-            // use_timeout ensures no leaky timeouts. Old timeout is always cleared.
-            let clear_timeout = with_owner(owner, {
+        // Clear previous timeout for key.
+        if let Some(timeout) = timeout_map.borrow_mut().remove(&query.key) {
+            timeout.clear();
+        }
+
+        if let Some(timeout) = maybe_time_until_stale(updated_at, cache_time) {
+            let evict = {
+                let client = client.clone();
+                let child_disposed = child_disposed.clone();
+                let held = held.clone();
+                let timeout_map = timeout_map.clone();
+                let cleanup_map = cleanup_map.clone();
                 let query = query.clone();
                 move || {
-                    use_timeout({
+                    timeout_map.borrow_mut().remove(&query.key);
+
+                    // Remove from cache & dispose.
+                    let dispose = {
                         let query = query.clone();
                         move || {
-                            if let Some(timeout) = maybe_time_until_stale(updated_at, cache_time) {
-                                let child_disposed = child_disposed.clone();
-                                let cleanup_map = cleanup_map.clone();
-                                let query = query.clone();
-
-                                set_timeout_with_handle(
-                                    move || {
-                                        // Remove from cache & dispose.
-                                        let dispose = {
-                                            let query = query.clone();
-                                            move || {
-                                                let removed = use_query_client()
-                                                    .evict_and_notify::<K, V>(&query.key);
-                                                if let Some(query) = removed {
-                                                    if query.observers.get() == 0 {
-                                                        query.dispose();
-                                                        drop(query)
-                                                    }
-                                                }
-                                            }
-                                        };
-
-                                        // Check if scope has been disposed. If it has, then dispose immediately. Otherwise add cleanup function.
-                                        if child_disposed.get() {
-                                            dispose();
-                                        } else {
-                                            let mut map = cleanup_map.borrow_mut();
-                                            map.insert(query.key.clone(), Box::new(dispose));
-                                        }
-                                    },
-                                    timeout,
-                                )
-                                .ok()
-                            } else {
-                                None
-                            }
+                            client.evict_and_notify::<K, V>(&query);
                         }
-                    })
-                }
-            });
+                    };
 
-            timeout_map.insert(query.key.clone(), Box::new(clear_timeout));
+                    // If the key is still held, wait until it no longer is. Otherwise dispose immediately.
+                    if child_disposed.get() || held.borrow().as_ref() != Some(&query.key) {
+                        dispose();
+                    } else {
+                        let mut map = cleanup_map.borrow_mut();
+                        map.insert(query.key.clone(), Box::new(dispose));
+                    }
+                }
+            };
+            let timeout = client.scheduler.schedule(evict, timeout);
+            timeout_map.borrow_mut().insert(query.key.clone(), timeout);
         }
     });
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        provide_query_client, provide_query_client_with, use_query, Instant, QueryClientBuilder,
+        QueryOptions, SsrPolicy,
+    };
+    use std::time::Duration;
+
+    #[test]
+    fn invalidating_during_fetch_refetches_once_afterwards() {
+        let _ = create_runtime();
+
+        crate::provide_query_client();
+        let client = use_query_client();
+
+        let (query, _) = client.get_or_create_query::<u32, u32>(0);
+        query.state.set(QueryState::Loading);
+
+        // Not invalid until the fetch completes.
+        assert!(!client.invalidate_query::<u32, u32>(0));
+        assert!(!client.invalidate_query::<u32, u32>(0));
+
+        spawn_local({
+            let client = client.clone();
+            let query = query.clone();
+            async move { fetch(&client, &query, |_| async { 1 }).await }
+        });
+
+        assert!(matches!(
+            query.state.get_untracked(),
+            QueryState::Invalid(QueryData { data: 1, .. })
+        ));
+        assert!(!query.invalidated_while_fetching.get());
+    }
+
+    #[test]
+    fn debounces_bursts_of_invalidations() {
+        let _ = create_runtime();
+
+        let debounce = Duration::from_secs(1);
+        provide_query_client_with(QueryClientBuilder::new().invalidation_debounce(debounce));
+        let client = use_query_client();
+
+        let fetches = Rc::new(Cell::new(0));
+        let query = use_query(
+            || 0_u32,
+            {
+                let fetches = fetches.clone();
+                move |_| {
+                    fetches.set(fetches.get() + 1);
+                    async { 1_u32 }
+                }
+            },
+            QueryOptions::default(),
+        );
+        create_isomorphic_effect(move |_| query.data.track());
+        assert_eq!(1, fetches.get());
+
+        assert!(client.invalidate_query::<u32, u32>(0));
+        assert!(!client.invalidate_query::<u32, u32>(0));
+        assert!(!client.invalidate_query::<u32, u32>(0));
+        assert_eq!(1, fetches.get());
+
+        client
+            .scheduler
+            .run_due(Instant(Instant::now().0 + debounce));
+        assert_eq!(2, fetches.get());
+        assert!(matches!(
+            client.cache::<u32, u32>().get(0),
+            Some(QueryState::Loaded(_))
+        ));
+
+        // Nothing else was scheduled.
+        client
+            .scheduler
+            .run_due(Instant(Instant::now().0 + debounce * 10));
+        assert_eq!(2, fetches.get());
+    }
+
+    #[test]
+    fn calls_observer_callbacks_once_per_fetch() {
+        let _ = create_runtime();
+
+        provide_query_client();
+        let client = use_query_client();
+
+        let calls = Rc::new(RefCell::new(Vec::new()));
+        let record = |name: &'static str| {
+            let calls = calls.clone();
+            move |data: &u32| calls.borrow_mut().push((name, *data))
+        };
+        let shared = QueryOptions::builder()
+            .on_success(record("success"))
+            .on_settled(record("settled"))
+            .build();
+        let fetched = Rc::new(Cell::new(0));
+        let mount = |options: QueryOptions<u32>| {
+            let fetched = fetched.clone();
+            let query = use_query(
+                || 0_u32,
+                move |_| {
+                    fetched.set(fetched.get() + 1);
+                    let data = fetched.get();
+                    async move { data }
+                },
+                options,
+            );
+            create_isomorphic_effect(move |_| query.data.track());
+            query
+        };
+
+        // Two observers sharing the same callbacks.
+        let query = mount(shared.clone());
+        mount(shared);
+        assert_eq!(
+            vec![("success", 1), ("settled", 1)],
+            *RefCell::borrow(&calls)
+        );
+
+        (query.refetch)();
+        assert_eq!(
+            vec![
+                ("success", 1),
+                ("settled", 1),
+                ("success", 2),
+                ("settled", 2)
+            ],
+            *RefCell::borrow(&calls)
+        );
+
+        // Writing the data isn't a fetch.
+        client.set_query_data::<u32, u32>(0, |_| Some(10));
+        assert_eq!(4, RefCell::borrow(&calls).len());
+    }
+
+    #[test]
+    fn skips_fetches_moments_after_a_fetch() {
+        let _ = create_runtime();
+
+        crate::provide_query_client();
+        let client = use_query_client();
+        Instant::freeze_now();
+
+        let fetches = Rc::new(Cell::new(0));
+        let mount = || {
+            client.fetch_query(
+                || 0_u32,
+                {
+                    let fetches = fetches.clone();
+                    move |_| {
+                        fetches.set(fetches.get() + 1);
+                        async { 1_u32 }
+                    }
+                },
+                true,
+            )
+        };
+        let result = mount();
+        assert_eq!(1, fetches.get());
+
+        // Such as a component mounting twice.
+        let _ = mount();
+        assert_eq!(1, fetches.get());
+        assert_eq!(1, client.deduplicated_fetches());
+
+        // Explicit refetches & invalidations are never skipped.
+        (result.refetch)();
+        client.invalidate_query::<u32, u32>(0);
+        assert_eq!(3, fetches.get());
+        assert_eq!(1, client.deduplicated_fetches());
+
+        // Mounting again later fetches the stale data.
+        Instant::advance_now(Duration::from_secs(1));
+        let _ = mount();
+        assert_eq!(4, fetches.get());
+        assert_eq!(1, client.deduplicated_fetches());
+    }
+
+    #[test]
+    fn quiet_refetch_keeps_state_while_fetching() {
+        let _ = create_runtime();
+
+        provide_query_client();
+        let client = use_query_client();
+
+        let (query, _) = client.get_or_create_query::<u32, u32>(0);
+        let fetching = Rc::new(Cell::new(None));
+        let result = client.fetch_query(
+            || 0_u32,
+            {
+                let fetching = fetching.clone();
+                move |_| {
+                    let state = query.state.get_untracked();
+                    fetching.set(Some(matches!(state, QueryState::Fetching(_))));
+                    async { 1_u32 }
+                }
+            },
+            true,
+        );
+
+        (result.refetch)();
+        assert_eq!(Some(true), fetching.get());
+
+        result.refetch_quietly();
+        assert_eq!(Some(false), fetching.get());
+        assert!(!result.is_fetching.get_untracked());
+        assert_eq!(Some(1), result.data.get_untracked());
+    }
+
+    #[test]
+    fn invalidating_during_quiet_fetch_invalidates_its_result() {
+        let _ = create_runtime();
+
+        provide_query_client();
+        let client = use_query_client();
+
+        client.set_query_data::<u32, u32>(0, |_| Some(1));
+        let (query, _) = client.get_or_create_query::<u32, u32>(0);
+        query.fetching_quietly.set(true);
+
+        // The state stays as is, but the fetch is still in flight.
+        assert!(!client.invalidate_query::<u32, u32>(0));
+        assert!(matches!(query.state.get_untracked(), QueryState::Loaded(_)));
+
+        spawn_local({
+            let client = client.clone();
+            let query = query.clone();
+            async move { fetch(&client, &query, |_| async { 2 }).await }
+        });
+
+        assert!(matches!(
+            query.state.get_untracked(),
+            QueryState::Invalid(QueryData { data: 2, .. })
+        ));
+        assert!(!query.fetching_quietly.get_untracked());
+    }
+
+    #[test]
+    fn quiet_refetch_is_forgotten_when_nothing_is_fetched() {
+        let _ = create_runtime();
+
+        provide_query_client();
+        let client = use_query_client();
+        client.set_query_data::<u32, u32>(0, |_| Some(1));
+
+        // Only fetched on the server, so refetching on the client does nothing.
+        let server_only = use_query(
+            || 0_u32,
+            |_| async { 2_u32 },
+            QueryOptions::builder().ssr(SsrPolicy::ServerOnly).build(),
+        );
+        server_only.refetch_quietly();
+
+        let (query, _) = client.get_or_create_query::<u32, u32>(0);
+        assert!(!query.quiet_next_fetch.get());
+        assert!(!query.refetch_requested.get());
+
+        // So the next fetch elsewhere isn't quiet.
+        let fetching = Rc::new(Cell::new(None));
+        let result = client.fetch_query(
+            || 0_u32,
+            {
+                let fetching = fetching.clone();
+                move |_| {
+                    let state = query.state.get_untracked();
+                    fetching.set(Some(matches!(state, QueryState::Fetching(_))));
+                    async { 3_u32 }
+                }
+            },
+            true,
+        );
+        (result.refetch)();
+        assert_eq!(Some(true), fetching.get());
+    }
+
+    #[test]
+    fn disposes_evicted_queries_once_unobserved() {
+        let _ = create_runtime();
+
+        provide_query_client();
+        let client = use_query_client();
+
+        // A long-lived app cycling through keys, evicting each one while it's observed.
+        for id in 0..1_000 {
+            client.set_query_data::<u32, u32>(id, move |_| Some(id));
+            let (query, _) = client.get_or_create_query::<u32, u32>(id);
+            let ((), observer) = as_child_of_current_owner(|query: Query<u32, u32>| {
+                synchronize_observer(Signal::derive(move || Some(query.clone())))
+            })(query.clone());
+
+            assert!(client.evict_and_notify(&query).is_some());
+            // Still observed, so its signals are kept.
+            assert!(query.state.try_get_untracked().is_some());
+
+            drop(observer);
+            assert!(query.state.try_get_untracked().is_none());
+        }
+        assert_eq!(0, client.cache::<u32, u32>().len());
+
+        // Unobserved queries are disposed right away.
+        let (query, _) = client.get_or_create_query::<u32, u32>(0);
+        assert!(client.evict_and_notify(&query).is_some());
+        assert!(query.state.try_get_untracked().is_none());
+    }
+
+    #[test]
+    fn evicts_expired_keys_once_the_observer_changes_keys() {
+        let _ = create_runtime();
+
+        provide_query_client();
+        let client = use_query_client();
+        Instant::freeze_now();
+
+        let id = create_rw_signal(0_u32);
+        let ((), observer) = as_child_of_current_owner(|()| {
+            let query = use_query(
+                move || id.get(),
+                |id| async move { id },
+                QueryOptions {
+                    stale_time: Some(Duration::from_secs(60)),
+                    cache_time: Some(Duration::from_secs(60)),
+                    ..QueryOptions::default()
+                },
+            );
+            create_isomorphic_effect(move |_| query.data.track());
+        })(());
+        let (first, _) = client.get_or_create_query::<u32, u32>(0);
+
+        // Expired, but still held.
+        Instant::advance_now(Duration::from_secs(61));
+        client.scheduler.run_due(Instant::now());
+        assert_eq!(Some(0), client.cache::<u32, u32>().get_data(0));
+
+        // Released, so it's evicted right away.
+        // The next keys are cached, as fetching from within the resource can't run natively.
+        client.set_query_data::<u32, u32>(1, |_| Some(1));
+        id.set(1);
+        assert_eq!(None, client.cache::<u32, u32>().get_data(0));
+        assert!(first.state.try_get_untracked().is_none());
+        assert_eq!(Some(1), client.cache::<u32, u32>().get_data(1));
+
+        // Released before it expired, so it's evicted once it does.
+        client.set_query_data::<u32, u32>(2, |_| Some(2));
+        id.set(2);
+        assert_eq!(Some(1), client.cache::<u32, u32>().get_data(1));
+        Instant::advance_now(Duration::from_secs(61));
+        client.scheduler.run_due(Instant::now());
+        assert_eq!(None, client.cache::<u32, u32>().get_data(1));
+
+        // The held key is kept until the observer is gone.
+        assert_eq!(Some(2), client.cache::<u32, u32>().get_data(2));
+        drop(observer);
+        assert_eq!(0, client.cache::<u32, u32>().len());
+    }
+
+    #[test]
+    fn cancelling_a_fetch_keeps_previous_data() {
+        let _ = create_runtime();
+
+        provide_query_client();
+        let client = use_query_client();
+
+        let (query, _) = client.get_or_create_query::<u32, u32>(0);
+        let data = QueryData::now(1);
+        query.state.set(QueryState::Fetching(data.clone()));
+
+        spawn_local({
+            let client = client.clone();
+            let query = query.clone();
+            async move {
+                fetch(&client, &query.clone(), move |_| async move {
+                    assert!(query.cancel());
+                    // Yield, so the cancellation is noticed before the fetch completes.
+                    let mut yielded = false;
+                    std::future::poll_fn(|cx| {
+                        if std::mem::replace(&mut yielded, true) {
+                            std::task::Poll::Ready(2)
+                        } else {
+                            cx.waker().wake_by_ref();
+                            std::task::Poll::Pending
+                        }
+                    })
+                    .await
+                })
+                .await
+            }
+        });
+
+        assert_eq!(QueryState::Loaded(data), query.state.get_untracked());
+        assert!(!query.cancel());
+    }
+
+    #[test]
+    fn switching_keys_offline_unpauses_the_previous_query() {
+        let _ = create_runtime();
+
+        provide_query_client_with(
+            QueryClientBuilder::new().online_manager(crate::OnlineManager::manual(false)),
+        );
+        let client = use_query_client();
+
+        let key = create_rw_signal(0_u32);
+        let query = client.get_query_signal::<u32, String>(move || key.get());
+        let query = Signal::derive(move || query.get().0);
+        let executor = create_executor(query, |num: u32| async move { num.to_string() });
+
+        let (first, _) = client.get_or_create_query::<u32, String>(0);
+        executor();
+        assert!(first.paused.get_untracked());
+
+        key.set(1);
+        executor();
+        let (second, _) = client.get_or_create_query::<u32, String>(1);
+        assert!(!first.paused.get_untracked());
+        assert!(second.paused.get_untracked());
+
+        client.online_manager().set_online(true);
+
+        assert!(!second.paused.get_untracked());
+        assert_eq!(Some(&"1".to_string()), second.state.get_untracked().data());
+        assert_eq!(QueryState::Created, first.state.get_untracked());
+    }
+}
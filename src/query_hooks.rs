@@ -0,0 +1,52 @@
+use std::{any::Any, rc::Rc, time::Duration};
+
+use crate::Instant;
+
+pub(crate) type OnQuerySuccess = Rc<dyn Fn(&QuerySuccess)>;
+
+/// Information about a query fetch that successfully wrote new data to the cache.
+///
+/// Passed to the global hook configured with [`QueryClientBuilder::on_query_success`](crate::QueryClientBuilder::on_query_success).
+#[derive(Clone)]
+pub struct QuerySuccess {
+    pub(crate) key: Rc<dyn Any>,
+    pub(crate) key_type: &'static str,
+    pub(crate) value_type: &'static str,
+    /// When the fetch started.
+    pub started_at: Instant,
+    /// When the fetched data was written to the cache.
+    pub updated_at: Instant,
+}
+
+impl QuerySuccess {
+    /// The key of the query, if it is of type `K`.
+    pub fn key<K: 'static>(&self) -> Option<&K> {
+        self.key.downcast_ref::<K>()
+    }
+
+    /// The type name of the query key.
+    pub fn key_type(&self) -> &'static str {
+        self.key_type
+    }
+
+    /// The type name of the query value.
+    pub fn value_type(&self) -> &'static str {
+        self.value_type
+    }
+
+    /// How long the fetch took.
+    pub fn duration(&self) -> Duration {
+        self.updated_at.0.saturating_sub(self.started_at.0)
+    }
+}
+
+impl std::fmt::Debug for QuerySuccess {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("QuerySuccess")
+            .field("key_type", &self.key_type)
+            .field("value_type", &self.value_type)
+            .field("started_at", &self.started_at)
+            .field("updated_at", &self.updated_at)
+            .finish()
+    }
+}
@@ -0,0 +1,141 @@
+use leptos::logging;
+
+/// Destination for the library's internal log messages.
+///
+/// Configure it with [`QueryClientBuilder::logger`](crate::QueryClientBuilder::logger).
+/// Defaults to [`LeptosLogger`]. Use [`SilentLogger`] to silence the library in production builds,
+/// or `TracingLogger` (with the `tracing` feature) to forward messages to `tracing`.
+pub trait QueryLogger {
+    /// Logs an informational message.
+    fn log(&self, message: &str);
+    /// Logs a warning.
+    fn warn(&self, message: &str);
+    /// Logs an error.
+    fn error(&self, message: &str);
+}
+
+/// Logs through Leptos' console logging. Messages go to the browser console, or stdout/stderr on the server.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct LeptosLogger;
+
+impl QueryLogger for LeptosLogger {
+    fn log(&self, message: &str) {
+        logging::log!("{message}")
+    }
+
+    fn warn(&self, message: &str) {
+        logging::warn!("{message}")
+    }
+
+    fn error(&self, message: &str) {
+        logging::error!("{message}")
+    }
+}
+
+/// Discards all messages.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct SilentLogger;
+
+impl QueryLogger for SilentLogger {
+    fn log(&self, _: &str) {}
+
+    fn warn(&self, _: &str) {}
+
+    fn error(&self, _: &str) {}
+}
+
+/// Forwards messages to [`tracing`](https://docs.rs/tracing) events.
+#[cfg(feature = "tracing")]
+#[derive(Clone, Copy, Debug, Default)]
+pub struct TracingLogger;
+
+#[cfg(feature = "tracing")]
+impl QueryLogger for TracingLogger {
+    fn log(&self, message: &str) {
+        tracing::info!(target: "leptos_query", "{message}")
+    }
+
+    fn warn(&self, message: &str) {
+        tracing::warn!(target: "leptos_query", "{message}")
+    }
+
+    fn error(&self, message: &str) {
+        tracing::error!(target: "leptos_query", "{message}")
+    }
+}
+
+/// Records messages, prefixed with their level, so tests can check what was logged.
+#[cfg(test)]
+#[derive(Clone, Default)]
+pub(crate) struct RecordingLogger(pub(crate) std::rc::Rc<std::cell::RefCell<Vec<String>>>);
+
+#[cfg(test)]
+impl RecordingLogger {
+    pub(crate) fn messages(&self) -> Vec<String> {
+        self.0.borrow().clone()
+    }
+
+    pub(crate) fn warnings(&self) -> Vec<String> {
+        self.0
+            .borrow()
+            .iter()
+            .filter_map(|message| message.strip_prefix("warn: "))
+            .map(str::to_string)
+            .collect()
+    }
+}
+
+#[cfg(test)]
+impl QueryLogger for RecordingLogger {
+    fn log(&self, message: &str) {
+        self.0.borrow_mut().push(format!("log: {message}"))
+    }
+
+    fn warn(&self, message: &str) {
+        self.0.borrow_mut().push(format!("warn: {message}"))
+    }
+
+    fn error(&self, message: &str) {
+        self.0.borrow_mut().push(format!("error: {message}"))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{provide_query_client_with, use_query, QueryClientBuilder, QueryOptions};
+    use leptos::*;
+
+    #[test]
+    #[cfg(debug_assertions)]
+    fn routes_library_messages_to_the_configured_logger() {
+        let _ = create_runtime();
+
+        let logger = RecordingLogger::default();
+        provide_query_client_with(QueryClientBuilder::new().logger(SilentLogger));
+        provide_query_client_with(QueryClientBuilder::new().logger(logger.clone()));
+
+        assert_eq!(1, logger.messages().len());
+        assert!(logger.messages()[0].starts_with("warn: A Query Client was provided below"));
+    }
+
+    #[test]
+    #[cfg(all(debug_assertions, not(any(feature = "ssr", feature = "hydrate"))))]
+    fn warns_about_missing_features_through_the_logger() {
+        let _ = create_runtime();
+
+        let logger = RecordingLogger::default();
+        provide_query_client_with(QueryClientBuilder::new().logger(logger.clone()));
+
+        let _ = use_query(
+            || 0_u32,
+            |_| async { "data".to_string() },
+            QueryOptions::default(),
+        );
+
+        assert!(logger
+            .warnings()
+            .iter()
+            .any(|warning| warning.contains("missing a Cargo feature")));
+    }
+}
@@ -1,30 +1,156 @@
-use std::time::Duration;
+use leptos::{create_effect, create_isomorphic_effect};
+use std::{rc::Rc, time::Duration};
+
+use crate::QueryMeta;
 
 /**
  * Options for a query [`crate::use_query::use_query`]
+ *
+ * Construct them with [`QueryOptions::builder`], or one of the presets such as [`QueryOptions::stale_time`].
  */
 #[derive(Clone)]
+#[non_exhaustive]
 pub struct QueryOptions<V> {
     /// Placeholder value to use while the query is loading for the first time.
+    #[deprecated(
+        since = "0.3.1",
+        note = "Use `initial_data`, which is computed lazily and stored in the cache."
+    )]
     pub default_value: Option<V>,
+    /// Computes the initial data of the query, if it has none yet.
+    /// The data is stored in the cache as if it were just fetched, so it is subject to `stale_time` like any other data.
+    ///
+    /// Only called when the query has no data, so it can be expensive,
+    /// or derive the data from other cached queries, such as a list that already contains the item.
+    /// Return [`None`](Option::None) to fetch as usual.
+    #[allow(clippy::type_complexity)]
+    pub initial_data: Option<Rc<dyn Fn() -> Option<V>>>,
     /// The duration that should pass before a query is considered stale.
     /// If the query is stale, it will be refetched.
-    /// If no stale time, the query will never be considered stale.
+    /// If no stale time, the query will never be considered stale, so it's never refetched unless invalidated or refetched manually.
+    /// An observer without a stale time doesn't constrain the merged stale time. Use [`INFINITE_TIME`] to require that the query never goes stale.
     /// Stale time is checked when [`QueryState::read`](#impl-<K,V>-for-QueryState<K,V>) is used.
     /// Stale time can never be greater than cache_time.
     /// Default is 0 milliseconds.
-    /// NOTE: If different stale times are used for the same key, the minimum time for the currently ACTIVE query will be used.
+    /// NOTE: If different stale times are used for the same key, the minimum time across the currently ACTIVE observers will be used.
     pub stale_time: Option<Duration>,
     /// The amount of time a query will be cached, once it's considered stale.
     /// If no cache time, the query will never be revoked from cache.
+    /// An observer without a cache time doesn't constrain the merged cache time. Use [`INFINITE_TIME`] to require that the query is never revoked.
     /// cache_time can never be less than stale_time.
     /// Default is 5 minutes.
-    /// NOTE: If different cache times are used for the same key, the minimum time will be used.
+    /// NOTE: If different cache times are used for the same key, the maximum time across the currently ACTIVE observers will be used.
     pub cache_time: Option<Duration>,
     /// If no refetch interval, the query will never refetch.
+    /// The query only refetches on an interval while it's observed with this option, so prefetched or abandoned queries don't keep polling.
+    /// NOTE: If different refetch intervals are used for the same key, the minimum interval across the currently ACTIVE observers will be used.
     pub refetch_interval: Option<Duration>,
+    /// If interval refetches are quiet, so they don't toggle [`is_fetching`](crate::QueryResult::is_fetching).
+    /// See [`QueryResult::refetch_quietly`](crate::QueryResult::refetch_quietly).
+    /// Default is false.
+    pub refetch_interval_quietly: bool,
+    /// If interval refetches are aligned to the wall clock, at multiples of the interval since the Unix epoch (UTC).
+    /// Such as the top of each minute for an interval of 1 minute, for data that is published on a schedule.
+    /// Otherwise, the query refetches once the interval has passed since its data was updated.
+    /// Default is false.
+    pub refetch_interval_aligned: bool,
+    /// If stale data is refetched when the app regains focus, while this query is observed with this option.
+    /// The focus status is provided by the client's [`FocusManager`](crate::FocusManager).
+    /// Default is false.
+    pub refetch_on_focus: bool,
     /// Determines which type of resource to use.
+    /// NOTE: If any of the currently ACTIVE observers of the same key is blocking, observers mounted while it is are blocking too.
     pub resource_option: ResourceOption,
+    /// Determines where the query is fetched, when using server side rendering.
+    /// Default is [`SsrPolicy::ServerAndClient`].
+    pub ssr: SsrPolicy,
+    /// Arbitrary metadata for the query, available to the fetcher through [`FetchContext`](crate::FetchContext).
+    pub meta: Option<QueryMeta>,
+    /// Called with the new data once every time a fetch for this query completes, while this query is observed with this option.
+    /// Observers sharing the same callback, such as the options of a [`QueryScope`](crate::QueryScope), only call it once per fetch.
+    /// Cancelled fetches don't call it.
+    #[allow(clippy::type_complexity)]
+    pub on_success: Option<Rc<dyn Fn(&V)>>,
+    /// Called with the new data once every time a fetch for this query settles, after [`on_success`](Self::on_success).
+    /// Fetchers can't fail, so every fetch that completes settles. For fetchers returning a [`Result`], it receives both `Ok` and `Err` values.
+    #[allow(clippy::type_complexity)]
+    pub on_settled: Option<Rc<dyn Fn(&V)>>,
+    /// Compares refetched data with the current data. When they're equal, only the update time changes,
+    /// and [`data`](crate::QueryResult::data) doesn't notify its subscribers, so polling data that rarely changes doesn't re-render.
+    /// See [`QueryOptionsBuilder::skip_unchanged_data`] and [`QueryOptionsBuilder::is_data_equal`].
+    #[allow(clippy::type_complexity)]
+    pub is_data_equal: Option<Rc<dyn Fn(&V, &V) -> bool>>,
+}
+
+/// The options a query is currently using, after merging the [`QueryOptions`] of all of its observers.
+///
+/// Retrieve it with [`QueryClient::get_query_options`](crate::QueryClient::get_query_options).
+/// The stale time & refetch interval are the minimum across the observers that set them, and the cache time is the maximum.
+/// Any blocking observer makes the query blocking. Without observers, the query keeps the stale time & cache time it last used,
+/// and has no refetch interval.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct EffectiveQueryOptions {
+    /// The effective stale time.
+    pub stale_time: Option<Duration>,
+    /// The effective cache time.
+    pub cache_time: Option<Duration>,
+    /// The effective refetch interval.
+    pub refetch_interval: Option<Duration>,
+    /// If any observer uses a [blocking](ResourceOption::Blocking) resource.
+    pub blocking: bool,
+    /// The number of observers whose options are merged.
+    pub observers: usize,
+}
+
+/// Determines where a query is fetched, when using server side rendering.
+///
+/// Used by [`QueryOptions::ssr`], [`QueryClient::fetch_query`](crate::QueryClient::fetch_query), and [`QueryClient::prefetch_query`](crate::QueryClient::prefetch_query).
+/// Whether the code runs on the server is determined by the `ssr` feature.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum SsrPolicy {
+    /// The query is fetched on the server, and on the client whenever it needs to be.
+    #[default]
+    ServerAndClient,
+    /// The query is only fetched on the client. The server renders it without data.
+    ClientOnly,
+    /// The query is only fetched on the server. The client uses the data rendered by the server, and never fetches it.
+    ServerOnly,
+}
+
+impl SsrPolicy {
+    /// If the query can be fetched where the code is running.
+    pub(crate) fn fetches_here(&self) -> bool {
+        match self {
+            SsrPolicy::ServerAndClient => true,
+            SsrPolicy::ClientOnly => !cfg!(feature = "ssr"),
+            SsrPolicy::ServerOnly => cfg!(feature = "ssr"),
+        }
+    }
+
+    // Creates an effect that only runs where the query can be fetched.
+    // Client only effects run after rendering, like `create_effect`.
+    pub(crate) fn create_effect<T: 'static>(self, f: impl Fn(Option<T>) -> T + 'static) {
+        match self {
+            _ if !self.fetches_here() => (),
+            SsrPolicy::ClientOnly => {
+                create_effect(f);
+            }
+            SsrPolicy::ServerAndClient | SsrPolicy::ServerOnly => {
+                create_isomorphic_effect(f);
+            }
+        }
+    }
+}
+
+/// `true` is [`SsrPolicy::ServerAndClient`], and `false` is [`SsrPolicy::ClientOnly`].
+impl From<bool> for SsrPolicy {
+    fn from(isomorphic: bool) -> Self {
+        if isomorphic {
+            SsrPolicy::ServerAndClient
+        } else {
+            SsrPolicy::ClientOnly
+        }
+    }
 }
 
 /// Determines which type of resource to use.
@@ -36,40 +162,257 @@ pub enum ResourceOption {
     Blocking,
 }
 
+#[allow(deprecated)]
 impl<V> QueryOptions<V> {
     /// Empty options.
     pub fn empty() -> Self {
         Self {
             default_value: None,
+            initial_data: None,
             stale_time: None,
             cache_time: None,
             refetch_interval: None,
+            refetch_interval_quietly: false,
+            refetch_interval_aligned: false,
+            refetch_on_focus: false,
             resource_option: ResourceOption::NonBlocking,
+            ssr: SsrPolicy::ServerAndClient,
+            meta: None,
+            on_success: None,
+            on_settled: None,
+            is_data_equal: None,
         }
     }
     /// QueryOption with custom stale_time.
     pub fn stale_time(stale_time: Duration) -> Self {
         Self {
             default_value: None,
+            initial_data: None,
             stale_time: Some(stale_time),
             cache_time: Some(DEFAULT_CACHE_TIME),
             refetch_interval: None,
+            refetch_interval_quietly: false,
+            refetch_interval_aligned: false,
+            refetch_on_focus: false,
             resource_option: ResourceOption::NonBlocking,
+            ssr: SsrPolicy::ServerAndClient,
+            meta: None,
+            on_success: None,
+            on_settled: None,
+            is_data_equal: None,
         }
     }
 
+    /// QueryOption for data that never changes, such as static configuration.
+    ///
+    /// The stale time and cache time are [`INFINITE_TIME`], so the data is fetched once and never becomes stale,
+    /// and it's only refetched when invalidated or refetched manually. It's also never evicted from the cache.
+    ///
+    /// NOTE: When merging the options of several observers, the minimum stale time and the maximum cache time are used.
+    /// The data never becomes stale only if no observer of the query uses a finite stale time.
+    #[doc(alias = "static_data")]
+    pub fn infinite() -> Self {
+        Self {
+            stale_time: Some(INFINITE_TIME),
+            cache_time: Some(INFINITE_TIME),
+            ..Self::empty()
+        }
+    }
+
+    /// QueryOption for data that is fetched once, and never refetched automatically while it's cached.
+    ///
+    /// The data never becomes stale, so it's only refetched when invalidated or refetched manually.
+    /// Unlike [`QueryOptions::infinite`], it's evicted from the cache once it's unused for the default cache time.
+    pub fn once() -> Self {
+        Self {
+            stale_time: None,
+            ..Self::default()
+        }
+    }
+
+    /// QueryOption for data that changes constantly, such as live dashboards.
+    ///
+    /// The data is stale right away, so it's refetched whenever a new observer mounts or the app regains focus,
+    /// and it's polled on the given interval while observed.
+    ///
+    /// Like the other presets, individual options can be overridden with a [`QueryOptionsBuilder`]:
+    /// ```
+    /// use leptos_query::*;
+    /// use std::time::Duration;
+    ///
+    /// let options: QueryOptions<u32> = QueryOptionsBuilder::from(QueryOptions::realtime(Duration::from_secs(5)))
+    ///     .refetch_interval_quietly(true)
+    ///     .build();
+    /// ```
+    pub fn realtime(refetch_interval: Duration) -> Self {
+        Self {
+            stale_time: Some(Duration::ZERO),
+            refetch_interval: Some(refetch_interval),
+            refetch_on_focus: true,
+            ..Self::default()
+        }
+    }
+
+    /// Creates a [`QueryOptionsBuilder`], starting from the [default options](QueryOptions::default).
+    ///
+    /// ```
+    /// use leptos_query::*;
+    /// use std::time::Duration;
+    ///
+    /// let options: QueryOptions<u32> = QueryOptions::builder()
+    ///     .stale_time(Duration::from_secs(5))
+    ///     .refetch_interval(Duration::from_secs(30))
+    ///     .build();
+    /// ```
+    pub fn builder() -> QueryOptionsBuilder<V> {
+        QueryOptionsBuilder::default()
+    }
+
     /// QueryOption with custom refetch_interval.
     pub fn refetch_interval(refetch_interval: Duration) -> Self {
         Self {
             default_value: None,
+            initial_data: None,
             stale_time: Some(DEFAULT_STALE_TIME),
             cache_time: Some(DEFAULT_CACHE_TIME),
             refetch_interval: Some(refetch_interval),
+            refetch_interval_quietly: false,
+            refetch_interval_aligned: false,
+            refetch_on_focus: false,
             resource_option: ResourceOption::NonBlocking,
+            ssr: SsrPolicy::ServerAndClient,
+            meta: None,
+            on_success: None,
+            on_settled: None,
+            is_data_equal: None,
+        }
+    }
+}
+
+/// Builds [`QueryOptions`]. Created with [`QueryOptions::builder`].
+///
+/// Options that aren't set keep their defaults. Start from a preset with [`QueryOptionsBuilder::from`].
+#[derive(Clone)]
+pub struct QueryOptionsBuilder<V> {
+    options: QueryOptions<V>,
+}
+
+impl<V> QueryOptionsBuilder<V> {
+    /// Sets the initial data. See [`QueryOptions::initial_data`].
+    pub fn initial_data(mut self, initial_data: impl Fn() -> Option<V> + 'static) -> Self {
+        self.options.initial_data = Some(Rc::new(initial_data));
+        self
+    }
+
+    /// Sets the stale time. [`None`](Option::None) means the data never becomes stale. See [`QueryOptions::stale_time`](QueryOptions#structfield.stale_time).
+    pub fn stale_time(mut self, stale_time: impl Into<Option<Duration>>) -> Self {
+        self.options.stale_time = stale_time.into();
+        self
+    }
+
+    /// Sets the cache time. [`None`](Option::None) means the data is never evicted. See [`QueryOptions::cache_time`].
+    pub fn cache_time(mut self, cache_time: impl Into<Option<Duration>>) -> Self {
+        self.options.cache_time = cache_time.into();
+        self
+    }
+
+    /// Sets the refetch interval. See [`QueryOptions::refetch_interval`](QueryOptions#structfield.refetch_interval).
+    pub fn refetch_interval(mut self, refetch_interval: impl Into<Option<Duration>>) -> Self {
+        self.options.refetch_interval = refetch_interval.into();
+        self
+    }
+
+    /// Sets if interval refetches are quiet. See [`QueryOptions::refetch_interval_quietly`].
+    pub fn refetch_interval_quietly(mut self, quietly: bool) -> Self {
+        self.options.refetch_interval_quietly = quietly;
+        self
+    }
+
+    /// Sets if interval refetches are aligned to the wall clock. See [`QueryOptions::refetch_interval_aligned`].
+    pub fn refetch_interval_aligned(mut self, aligned: bool) -> Self {
+        self.options.refetch_interval_aligned = aligned;
+        self
+    }
+
+    /// Sets if stale data is refetched when the app regains focus. See [`QueryOptions::refetch_on_focus`].
+    pub fn refetch_on_focus(mut self, refetch_on_focus: bool) -> Self {
+        self.options.refetch_on_focus = refetch_on_focus;
+        self
+    }
+
+    /// Sets the resource option. See [`QueryOptions::resource_option`].
+    pub fn resource_option(mut self, resource_option: ResourceOption) -> Self {
+        self.options.resource_option = resource_option;
+        self
+    }
+
+    /// Sets where the query is fetched. See [`QueryOptions::ssr`].
+    pub fn ssr(mut self, ssr: impl Into<SsrPolicy>) -> Self {
+        self.options.ssr = ssr.into();
+        self
+    }
+
+    /// Sets the metadata. See [`QueryOptions::meta`].
+    pub fn meta(mut self, meta: QueryMeta) -> Self {
+        self.options.meta = Some(meta);
+        self
+    }
+
+    /// Sets the success callback. See [`QueryOptions::on_success`].
+    pub fn on_success(mut self, on_success: impl Fn(&V) + 'static) -> Self {
+        self.options.on_success = Some(Rc::new(on_success));
+        self
+    }
+
+    /// Sets the settled callback. See [`QueryOptions::on_settled`].
+    pub fn on_settled(mut self, on_settled: impl Fn(&V) + 'static) -> Self {
+        self.options.on_settled = Some(Rc::new(on_settled));
+        self
+    }
+
+    /// Refetched data that is equal to the current data doesn't notify the subscribers of [`data`](crate::QueryResult::data).
+    /// The update time still changes, so the data is fresh again. See [`QueryOptions::is_data_equal`].
+    pub fn skip_unchanged_data(self) -> Self
+    where
+        V: PartialEq,
+    {
+        self.is_data_equal(|data, other| data == other)
+    }
+
+    /// Sets the comparison of refetched data, for data that isn't [`PartialEq`] or has fields that don't matter.
+    /// See [`QueryOptions::is_data_equal`].
+    pub fn is_data_equal(mut self, is_data_equal: impl Fn(&V, &V) -> bool + 'static) -> Self {
+        self.options.is_data_equal = Some(Rc::new(is_data_equal));
+        self
+    }
+
+    /// Builds the options.
+    pub fn build(self) -> QueryOptions<V> {
+        self.options
+    }
+}
+
+impl<V> Default for QueryOptionsBuilder<V> {
+    fn default() -> Self {
+        Self {
+            options: QueryOptions::default(),
         }
     }
 }
 
+/// Starts from existing options, such as a preset.
+impl<V> From<QueryOptions<V>> for QueryOptionsBuilder<V> {
+    fn from(options: QueryOptions<V>) -> Self {
+        Self { options }
+    }
+}
+
+impl<V> From<QueryOptionsBuilder<V>> for QueryOptions<V> {
+    fn from(builder: QueryOptionsBuilder<V>) -> Self {
+        builder.build()
+    }
+}
+
 pub(crate) fn ensure_valid_stale_time(
     stale_time: &Option<Duration>,
     cache_time: &Option<Duration>,
@@ -86,17 +429,156 @@ pub(crate) fn ensure_valid_stale_time(
     }
 }
 
+/// A stale time or cache time that never elapses. Used by [`QueryOptions::infinite`].
+///
+/// Unlike [`None`](Option::None), it's kept when merging the options of several observers,
+/// so [`QueryClient::get_query_options`](crate::QueryClient::get_query_options) reports it.
+pub const INFINITE_TIME: Duration = Duration::MAX;
+
 const DEFAULT_STALE_TIME: Duration = Duration::from_secs(0);
 const DEFAULT_CACHE_TIME: Duration = Duration::from_secs(60 * 5);
 
+#[allow(deprecated)]
 impl<V> Default for QueryOptions<V> {
     fn default() -> Self {
         Self {
             default_value: None,
+            initial_data: None,
             stale_time: Some(DEFAULT_STALE_TIME),
             cache_time: Some(DEFAULT_CACHE_TIME),
             refetch_interval: None,
+            refetch_interval_quietly: false,
+            refetch_interval_aligned: false,
+            refetch_on_focus: false,
             resource_option: ResourceOption::NonBlocking,
+            ssr: SsrPolicy::ServerAndClient,
+            meta: None,
+            on_success: None,
+            on_settled: None,
+            is_data_equal: None,
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        instant::Instant, provide_query_client, provide_query_client_with, use_query,
+        use_query_client, FocusManager, QueryClientBuilder,
+    };
+    use leptos::*;
+    use std::cell::Cell;
+
+    #[test]
+    fn infinite_data_never_goes_stale_or_gets_evicted() {
+        let _ = create_runtime();
+
+        provide_query_client();
+        let client = use_query_client();
+        Instant::freeze_now();
+
+        let fetches = Rc::new(Cell::new(0));
+        let mount = |options: QueryOptions<u32>| {
+            let fetches = fetches.clone();
+            let query = use_query(
+                || 0_u32,
+                move |_| {
+                    fetches.set(fetches.get() + 1);
+                    async { 1_u32 }
+                },
+                options,
+            );
+            create_isomorphic_effect(move |_| query.data.track());
+        };
+        let options = || client.get_query_options::<u32, u32>(0).unwrap();
+
+        mount(QueryOptions::infinite());
+        // An observer without a stale time or cache time doesn't constrain them.
+        mount(QueryOptions::empty());
+        assert_eq!(
+            (Some(INFINITE_TIME), Some(INFINITE_TIME), 2),
+            (
+                options().stale_time,
+                options().cache_time,
+                options().observers
+            )
+        );
+
+        // Much later, the data is still cached, and mounting doesn't refetch it.
+        Instant::advance_now(Duration::from_secs(60 * 60 * 24 * 365));
+        client.scheduler.run_due(Instant::now());
+        mount(QueryOptions::infinite());
+        assert_eq!(1, fetches.get());
+        assert_eq!(1, client.size().get_untracked());
+
+        // An observer with a finite stale time constrains it. The longest cache time is kept.
+        mount(QueryOptions::default());
+        assert_eq!(
+            (Some(DEFAULT_STALE_TIME), Some(INFINITE_TIME)),
+            (options().stale_time, options().cache_time)
+        );
+        assert_eq!(2, fetches.get());
+    }
+
+    #[test]
+    fn realtime_data_is_refetched_on_focus_and_once_data_is_not() {
+        let _ = create_runtime();
+
+        let focus_manager = FocusManager::manual(true);
+        provide_query_client_with(QueryClientBuilder::new().focus_manager(focus_manager));
+        let client = use_query_client();
+        Instant::freeze_now();
+
+        let fetches = Rc::new(Cell::new([0, 0]));
+        let mount = |key: usize, options: QueryOptions<u32>| {
+            let fetches = fetches.clone();
+            let query = use_query(
+                move || key,
+                move |key| {
+                    let mut counts = fetches.get();
+                    counts[key] += 1;
+                    fetches.set(counts);
+                    async { 1_u32 }
+                },
+                options,
+            );
+            create_isomorphic_effect(move |_| query.data.track());
+        };
+
+        mount(0, QueryOptions::realtime(Duration::from_secs(60)));
+        mount(1, QueryOptions::once());
+        assert_eq!([1, 1], fetches.get());
+
+        let options = client.get_query_options::<usize, u32>(1).unwrap();
+        assert_eq!(
+            (None, Some(DEFAULT_CACHE_TIME)),
+            (options.stale_time, options.cache_time)
+        );
+
+        Instant::advance_now(Duration::from_secs(60 * 60));
+        focus_manager.set_focused(false);
+        focus_manager.set_focused(true);
+        assert_eq!([2, 1], fetches.get());
+
+        // Mounting again doesn't refetch data that never goes stale.
+        mount(1, QueryOptions::once());
+        assert_eq!([2, 1], fetches.get());
+    }
+
+    #[test]
+    fn ssr_policy_decides_where_queries_are_fetched() {
+        let _ = create_runtime();
+
+        provide_query_client();
+        let client = use_query_client();
+
+        let on_server = cfg!(feature = "ssr");
+        let server_only = client.fetch_query(|| 0_u32, |_| async { 1_u32 }, SsrPolicy::ServerOnly);
+        let client_only = client.fetch_query(|| 1_u32, |_| async { 1_u32 }, SsrPolicy::ClientOnly);
+
+        assert_eq!(on_server, server_only.data.get_untracked().is_some());
+        assert_eq!(!on_server, client_only.data.get_untracked().is_some());
+        assert_eq!(SsrPolicy::ClientOnly, SsrPolicy::from(false));
+    }
+}
@@ -1,9 +1,17 @@
-use std::time::Duration;
+use std::{
+    cell::{Cell, RefCell},
+    future::{poll_fn, Future},
+    hash::Hash,
+    rc::Rc,
+    task::{Poll, Waker},
+    time::Duration,
+};
 
 use crate::{
     query::Query,
+    use_query_client,
     util::{maybe_time_until_stale, use_timeout},
-    QueryState,
+    Instant, QueryClient, QueryData, QueryResource, QueryState,
 };
 use leptos::*;
 
@@ -22,74 +30,1159 @@ where
 
     /// If the query is fetching for the first time.
     pub is_loading: Signal<bool>,
+    /// If the query has no data yet, and is going to fetch it.
+    /// Unlike [`is_loading`](Self::is_loading), it is also true before the first fetch starts.
+    /// Always false for queries that can't fetch yet, such as a [dependent query](crate::use_dependent_query) whose key isn't resolved,
+    /// so they don't show a spinner forever.
+    pub is_initial_loading: Signal<bool>,
     /// If the query is actively fetching.
     pub is_fetching: Signal<bool>,
     /// If the query data is considered stale.
     pub is_stale: Signal<bool>,
     /// If the query data has been marked as invalid.
     pub is_invalid: Signal<bool>,
+    /// If the query wants to fetch, but is waiting for the network to come back.
+    /// See [`OnlineManager`](crate::OnlineManager).
+    pub is_paused: Signal<bool>,
+    /// The progress of the current fetch, from `0.0` to `1.0`, if the fetcher reports it.
+    /// See [`FetchContext::report_progress`](crate::FetchContext::report_progress).
+    pub progress: Signal<Option<f32>>,
 
     /// Refetch the query.
     pub refetch: R,
+
+    pub(crate) resource: Option<QueryResource<V>>,
+    pub(crate) handle: Option<Rc<dyn QueryHandle<V>>>,
+    // When the data was last updated, read without cloning the data.
+    pub(crate) updated_at: Signal<Option<Instant>>,
+}
+
+impl<V, R> QueryResult<V, R>
+where
+    V: Clone + 'static,
+    R: RefetchFn,
+{
+    /// The [`Resource`] that drives [`data`](Self::data), for composing with Leptos' own resource utilities,
+    /// such as [`loading`](Resource::loading) or [`Suspense`](leptos::Suspense) coordination.
+    ///
+    /// Only results created with [`use_query`](crate::use_query()) are backed by a resource.
+    /// Returns [`None`](Option::None) otherwise.
+    ///
+    /// Example:
+    /// ```no_run
+    /// use leptos::*;
+    /// use leptos_query::*;
+    /// # #[derive(Clone, serde::Deserialize, serde::Serialize)]
+    /// # struct Monkey { name: String }
+    /// # async fn get_monkey(id: u32) -> Monkey { todo!() }
+    ///
+    /// let query = use_query(|| 1, get_monkey, QueryOptions::default());
+    /// let resource = query.resource().expect("created by use_query");
+    /// let is_pending = move || resource.loading().get();
+    /// ```
+    pub fn resource(&self) -> Option<QueryResource<V>> {
+        self.resource
+    }
+
+    /// The data as a [`Memo`], for reading fields of large data without cloning all of it on each access.
+    ///
+    /// Each call creates a new memo, which holds a single copy of the data. Create it once, then read it with [`with`](SignalWith::with).
+    /// Subscribers are only notified when the data changes.
+    ///
+    /// Example:
+    /// ```no_run
+    /// use leptos::*;
+    /// use leptos_query::*;
+    /// # #[derive(Clone, PartialEq, serde::Deserialize, serde::Serialize)]
+    /// # struct Zoo { name: String, monkeys: Vec<String> }
+    /// # async fn get_zoo(id: u32) -> Zoo { todo!() }
+    ///
+    /// let zoo = use_query(|| 1, get_zoo, QueryOptions::default()).data_memo();
+    /// let name = move || zoo.with(|zoo| zoo.as_ref().map(|zoo| zoo.name.clone()));
+    /// let monkey_count = move || zoo.with(|zoo| zoo.as_ref().map_or(0, |zoo| zoo.monkeys.len()));
+    /// ```
+    pub fn data_memo(&self) -> Memo<Option<V>>
+    where
+        V: PartialEq,
+    {
+        let data = self.data;
+        create_memo(move |_| data.get())
+    }
+
+    /// Reads the data by reference, without cloning it. Tracks the data, like [`data`](Self::data).
+    ///
+    /// Reads the query's cached data directly, so unlike [`data`](Self::data), reading it never starts a fetch.
+    /// Results derived with [`map`](Self::map) or [`zip`](Self::zip) don't have cached data of their own, and clone their derived data.
+    ///
+    /// Example:
+    /// ```no_run
+    /// use leptos::*;
+    /// use leptos_query::*;
+    /// # #[derive(Clone, serde::Deserialize, serde::Serialize)]
+    /// # struct Zoo { name: String, monkeys: Vec<String> }
+    /// # async fn get_zoo(id: u32) -> Zoo { todo!() }
+    ///
+    /// let zoo = use_query(|| 1, get_zoo, QueryOptions::default());
+    /// let monkey_count = move || zoo.with_data(|zoo| zoo.map_or(0, |zoo| zoo.monkeys.len()));
+    /// ```
+    pub fn with_data<T>(&self, f: impl FnOnce(Option<&V>) -> T) -> T {
+        match &self.handle {
+            Some(handle) => {
+                let mut f = Some(f);
+                let mut result = None;
+                handle.with_data(&mut |data| result = f.take().map(|f| f(data)));
+                result.expect("with_data calls its closure once")
+            }
+            None => self.data.with(|data| f(data.as_ref())),
+        }
+    }
+
+    /// Renders the data, for simple pages where displaying each state of the query is overkill.
+    ///
+    /// The view is wrapped in a [`Transition`], which shows "Loading..." until the data is first loaded,
+    /// and keeps showing the current data while refetching. For queries whose fetcher returns a [`Result`], see [`render_ok`](QueryResult::render_ok).
+    /// To customize the fallback, use [`render_with_fallback`](QueryResult::render_with_fallback).
+    ///
+    /// Example:
+    /// ```no_run
+    /// use leptos::*;
+    /// use leptos_query::*;
+    /// # #[derive(Clone, serde::Deserialize, serde::Serialize)]
+    /// # struct Monkey { name: String }
+    /// # async fn get_monkey(id: u32) -> Monkey { todo!() }
+    ///
+    /// #[component]
+    /// fn MonkeyName(id: u32) -> impl IntoView {
+    ///     let query = use_query(move || id, get_monkey, QueryOptions::default());
+    ///     query.render(|monkey| view! { <h2>{monkey.name}</h2> })
+    /// }
+    /// ```
+    pub fn render<IV>(&self, view: impl Fn(V) -> IV + 'static) -> impl IntoView
+    where
+        IV: IntoView,
+    {
+        self.render_with_fallback(|| "Loading...", view)
+    }
+
+    /// Renders the data like [`render`](QueryResult::render), showing the given fallback until the data is first loaded.
+    ///
+    /// The fallback is also shown while there is no data, for results that don't suspend a [`Transition`],
+    /// such as results of [`QueryClient::fetch_query`](crate::QueryClient::fetch_query).
+    ///
+    /// Example:
+    /// ```no_run
+    /// use leptos::*;
+    /// use leptos_query::*;
+    /// # #[derive(Clone, serde::Deserialize, serde::Serialize)]
+    /// # struct Monkey { name: String }
+    /// # async fn get_monkey(id: u32) -> Monkey { todo!() }
+    ///
+    /// #[component]
+    /// fn MonkeyName(id: u32) -> impl IntoView {
+    ///     let query = use_query(move || id, get_monkey, QueryOptions::default());
+    ///     query.render_with_fallback(
+    ///         || view! { <p>"Finding the monkey..."</p> },
+    ///         |monkey| view! { <h2>{monkey.name}</h2> },
+    ///     )
+    /// }
+    /// ```
+    pub fn render_with_fallback<IV>(
+        &self,
+        fallback: impl Into<ViewFn>,
+        view: impl Fn(V) -> IV + 'static,
+    ) -> impl IntoView
+    where
+        IV: IntoView,
+    {
+        let data = self.data;
+        let fallback: ViewFn = fallback.into();
+        let view = Rc::new(view);
+        view! {
+            <Transition fallback={
+                let fallback = fallback.clone();
+                move || fallback.run()
+            }>
+                {
+                    let (fallback, view) = (fallback.clone(), view.clone());
+                    move || match data.get() {
+                        Some(data) => view(data).into_view(),
+                        None => fallback.run(),
+                    }
+                }
+            </Transition>
+        }
+    }
+
+    /// Sets the data of the query's current key, as if it was just fetched.
+    /// Shorthand for [`QueryClient::set_query_data`](crate::QueryClient::set_query_data).
+    ///
+    /// Results derived with [`map`](Self::map) or [`zip`](Self::zip) aren't bound to a key, so this does nothing for them.
+    ///
+    /// Example:
+    /// ```no_run
+    /// use leptos::*;
+    /// use leptos_query::*;
+    /// # #[derive(Clone, serde::Deserialize, serde::Serialize)]
+    /// # struct Monkey { name: String }
+    /// # async fn get_monkey(id: u32) -> Monkey { todo!() }
+    ///
+    /// let query = use_query(|| 1, get_monkey, QueryOptions::default());
+    /// query.set_data(Monkey { name: "George".to_string() });
+    /// ```
+    pub fn set_data(&self, data: V) {
+        if let Some(handle) = &self.handle {
+            handle.update_data(Box::new(move |_| Some(data)));
+        }
+    }
+
+    /// Invalidates the query's current key, so it is refetched.
+    /// Shorthand for [`QueryClient::invalidate_query`](crate::QueryClient::invalidate_query).
+    ///
+    /// Returns true if the query was invalidated. Always false for results derived with [`map`](Self::map) or [`zip`](Self::zip).
+    pub fn invalidate(&self) -> bool {
+        self.handle
+            .as_ref()
+            .is_some_and(|handle| handle.invalidate())
+    }
+
+    /// Removes the query's current key from the cache.
+    ///
+    /// This result keeps its current state, but the query is no longer shared:
+    /// the next component to use the key fetches it anew.
+    ///
+    /// Returns true if the query was removed. Always false for results derived with [`map`](Self::map) or [`zip`](Self::zip).
+    pub fn remove(&self) -> bool {
+        self.handle.as_ref().is_some_and(|handle| handle.remove())
+    }
+
+    /// Cancels the current fetch of the query's current key, such as for a "stop loading" button.
+    ///
+    /// The fetcher's future is dropped and its result discarded. The data is left intact:
+    /// a query that was refetching keeps its previous data, and a query that was loading for the first time has none.
+    /// A fetch waiting for the network is cancelled as well.
+    ///
+    /// Returns true if a fetch was cancelled. Always false for results derived with [`map`](Self::map) or [`zip`](Self::zip).
+    pub fn cancel(&self) -> bool {
+        self.handle.as_ref().is_some_and(|handle| handle.cancel())
+    }
+
+    /// Refetches the query without toggling [`is_fetching`](Self::is_fetching): its state stays as is until the new data arrives.
+    /// For UIs where even a subtle fetching indicator is unwanted flicker, such as a periodic refresh.
+    ///
+    /// A query without data loads as usual. Interval refetches can be made quiet with [`QueryOptions::refetch_interval_quietly`](crate::QueryOptions::refetch_interval_quietly).
+    /// Results derived with [`map`](Self::map) or [`zip`](Self::zip) refetch as usual.
+    pub fn refetch_quietly(&self) {
+        match &self.handle {
+            Some(handle) => handle.refetch_quietly(),
+            None => (self.refetch)(),
+        }
+    }
+
+    /// Derives a result whose data is transformed by `func`. The state flags & refetch function are shared with this result.
+    ///
+    /// Example:
+    /// ```no_run
+    /// use leptos::*;
+    /// use leptos_query::*;
+    /// # #[derive(Clone, serde::Deserialize, serde::Serialize)]
+    /// # struct Monkey { name: String }
+    /// # async fn get_monkeys() -> Vec<Monkey> { todo!() }
+    ///
+    /// let monkeys = use_query(|| (), |_| get_monkeys(), QueryOptions::default());
+    /// let monkey_count = monkeys.map(|monkeys| monkeys.len());
+    /// ```
+    pub fn map<U>(&self, func: impl Fn(&V) -> U + 'static) -> QueryResult<U, R>
+    where
+        U: Clone + 'static,
+    {
+        let func = Rc::new(func);
+        let data = self.data;
+        let state = self.state;
+        QueryResult {
+            data: Signal::derive({
+                let func = func.clone();
+                move || data.with(|data| data.as_ref().map(|data| func(data)))
+            }),
+            state: Signal::derive(move || state.with(|state| map_state(state, |data| func(data)))),
+            is_loading: self.is_loading,
+            is_initial_loading: self.is_initial_loading,
+            is_fetching: self.is_fetching,
+            is_stale: self.is_stale,
+            is_invalid: self.is_invalid,
+            is_paused: self.is_paused,
+            progress: self.progress,
+            refetch: self.refetch.clone(),
+            resource: None,
+            handle: None,
+            updated_at: self.updated_at,
+        }
+    }
+
+    /// Combines this result with another one. The combined result has data once both have data.
+    ///
+    /// It is loading, fetching, stale, invalid, or paused if either result is.
+    /// Its progress is the lowest progress reported by either result.
+    /// Its data is as old as the older of the two, and refetching refetches both.
+    ///
+    /// Example:
+    /// ```no_run
+    /// use leptos::*;
+    /// use leptos_query::*;
+    /// # #[derive(Clone, serde::Deserialize, serde::Serialize)]
+    /// # struct Monkey { name: String }
+    /// # #[derive(Clone, serde::Deserialize, serde::Serialize)]
+    /// # struct Zoo { name: String }
+    /// # async fn get_monkey(id: u32) -> Monkey { todo!() }
+    /// # async fn get_zoo(id: u32) -> Zoo { todo!() }
+    ///
+    /// let monkey = use_query(|| 1, get_monkey, QueryOptions::default());
+    /// let zoo = use_query(|| 2, get_zoo, QueryOptions::default());
+    ///
+    /// let QueryResult { data, is_loading, .. } = monkey.zip(&zoo);
+    /// ```
+    pub fn zip<W, S>(&self, other: &QueryResult<W, S>) -> QueryResult<(V, W), impl RefetchFn>
+    where
+        W: Clone + 'static,
+        S: RefetchFn,
+    {
+        let (data, other_data) = (self.data, other.data);
+        let (state, other_state) = (self.state, other.state);
+        let either = |a: Signal<bool>, b: Signal<bool>| Signal::derive(move || a.get() || b.get());
+        let (refetch, other_refetch) = (self.refetch.clone(), other.refetch.clone());
+        let (progress, other_progress) = (self.progress, other.progress);
+        let (updated_at, other_updated_at) = (self.updated_at, other.updated_at);
+
+        QueryResult {
+            data: Signal::derive(move || data.get().zip(other_data.get())),
+            state: Signal::derive(move || {
+                state.with(|state| other_state.with(|other_state| zip_state(state, other_state)))
+            }),
+            is_loading: either(self.is_loading, other.is_loading),
+            is_initial_loading: either(self.is_initial_loading, other.is_initial_loading),
+            is_fetching: either(self.is_fetching, other.is_fetching),
+            is_stale: either(self.is_stale, other.is_stale),
+            is_invalid: either(self.is_invalid, other.is_invalid),
+            is_paused: either(self.is_paused, other.is_paused),
+            progress: Signal::derive(move || match (progress.get(), other_progress.get()) {
+                (Some(progress), Some(other_progress)) => Some(progress.min(other_progress)),
+                (progress, other_progress) => progress.or(other_progress),
+            }),
+            refetch: move || {
+                refetch();
+                other_refetch();
+            },
+            resource: None,
+            handle: None,
+            updated_at: Signal::derive(move || {
+                let (updated_at, other_updated_at) = (updated_at.get()?, other_updated_at.get()?);
+                Some(updated_at.min(other_updated_at))
+            }),
+        }
+    }
+
+    /// The time elapsed since the data was last updated, for labels such as "last updated 37s ago".
+    /// [`None`](Option::None) while the query has no data.
+    ///
+    /// Updates whenever the data does, and on every tick in between. Use a coarse tick, such as a second, for labels.
+    ///
+    /// Example:
+    /// ```no_run
+    /// use leptos::*;
+    /// use leptos_query::*;
+    /// use std::time::Duration;
+    /// # #[derive(Clone, serde::Deserialize, serde::Serialize)]
+    /// # struct Monkey { name: String }
+    /// # async fn get_monkey(id: u32) -> Monkey { todo!() }
+    ///
+    /// let query = use_query(|| 1, get_monkey, QueryOptions::default());
+    /// let since_update = query.time_since_update(Duration::from_secs(1));
+    /// let label = move || since_update.get().map(|since| format!("Last updated {}s ago", since.as_secs()));
+    /// ```
+    pub fn time_since_update(&self, tick: Duration) -> Signal<Option<Duration>> {
+        let updated_at = self.updated_at;
+        let ticks = create_trigger();
+        let scheduler = use_query_client().scheduler;
+
+        let _ = use_timeout(move || {
+            ticks.track();
+            updated_at
+                .get()
+                .map(|_| scheduler.schedule(move || ticks.notify(), tick))
+        });
+
+        Signal::derive(move || {
+            ticks.track();
+            updated_at
+                .get()
+                .map(|updated_at| Instant::now().0.saturating_sub(updated_at.0))
+        })
+    }
+
+    /// Returns a future that resolves with the query's data, once it is available.
+    ///
+    /// Can be awaited inside async blocks in views, such as the future of an [`Await`](leptos::Await) component,
+    /// instead of matching on [`data`](Self::data). Reading the data starts the first fetch, if needed.
+    /// The future must be created within a reactive scope, and only resolves while that scope is alive.
+    ///
+    /// Example:
+    /// ```no_run
+    /// use leptos::*;
+    /// use leptos_query::*;
+    /// # #[derive(Clone, serde::Deserialize, serde::Serialize)]
+    /// # struct Monkey { name: String }
+    /// # async fn get_monkey(id: u32) -> Monkey { todo!() }
+    ///
+    /// let query = use_query(|| 1, get_monkey, QueryOptions::default());
+    /// let name = async move { query.suspend().await.name };
+    /// ```
+    pub fn suspend(&self) -> impl Future<Output = V> {
+        let suspended = Rc::new(RefCell::new(Suspended {
+            value: None,
+            waker: None,
+        }));
+
+        let data = self.data;
+        create_isomorphic_effect({
+            let suspended = suspended.clone();
+            move |_| {
+                if let Some(value) = data.get() {
+                    let mut suspended = suspended.borrow_mut();
+                    suspended.value = Some(value);
+                    if let Some(waker) = suspended.waker.take() {
+                        waker.wake();
+                    }
+                }
+            }
+        });
+
+        poll_fn(move |cx| {
+            let mut suspended = suspended.borrow_mut();
+            match suspended.value.take() {
+                Some(value) => Poll::Ready(value),
+                None => {
+                    suspended.waker = Some(cx.waker().clone());
+                    Poll::Pending
+                }
+            }
+        })
+    }
+}
+
+/// Accessors for queries whose fetcher returns a [`Result`].
+///
+/// Example:
+/// ```no_run
+/// use leptos::*;
+/// use leptos_query::*;
+/// # #[derive(Clone, serde::Deserialize, serde::Serialize)]
+/// # struct Monkey { name: String }
+/// # async fn get_monkey(id: u32) -> Result<Monkey, String> { todo!() }
+///
+/// let query = use_query(|| 1, get_monkey, QueryOptions::default());
+/// let monkey = query.data_ok();
+/// let error = query.error();
+/// ```
+impl<T, E, R> QueryResult<Result<T, E>, R>
+where
+    T: Clone + 'static,
+    E: Clone + 'static,
+    R: RefetchFn,
+{
+    /// The data, if the query has data and it is [`Ok`].
+    pub fn data_ok(&self) -> Signal<Option<T>> {
+        let data = self.data;
+        Signal::derive(move || {
+            data.with(|data| {
+                data.as_ref()
+                    .and_then(|result| result.as_ref().ok().cloned())
+            })
+        })
+    }
+
+    /// The error, if the query has data and it is an [`Err`].
+    pub fn error(&self) -> Signal<Option<E>> {
+        let data = self.data;
+        Signal::derive(move || {
+            data.with(|data| {
+                data.as_ref()
+                    .and_then(|result| result.as_ref().err().cloned())
+            })
+        })
+    }
+
+    /// The data if it is [`Ok`], or the given default while the query has no data or it is an [`Err`].
+    pub fn unwrap_or(&self, default: T) -> Signal<T> {
+        let data = self.data_ok();
+        Signal::derive(move || data.get().unwrap_or_else(|| default.clone()))
+    }
+
+    /// Renders the data if it is [`Ok`], and the error with `error_view` if it is an [`Err`]. See [`render`](QueryResult::render).
+    ///
+    /// Example:
+    /// ```no_run
+    /// use leptos::*;
+    /// use leptos_query::*;
+    /// # #[derive(Clone, serde::Deserialize, serde::Serialize)]
+    /// # struct Monkey { name: String }
+    /// # async fn get_monkey(id: u32) -> Result<Monkey, String> { todo!() }
+    ///
+    /// #[component]
+    /// fn MonkeyName(id: u32) -> impl IntoView {
+    ///     let query = use_query(move || id, get_monkey, QueryOptions::default());
+    ///     query.render_ok(
+    ///         |monkey| view! { <h2>{monkey.name}</h2> },
+    ///         |error| view! { <p class="error">{error}</p> },
+    ///     )
+    /// }
+    /// ```
+    pub fn render_ok<IV, EV>(
+        &self,
+        view: impl Fn(T) -> IV + 'static,
+        error_view: impl Fn(E) -> EV + 'static,
+    ) -> impl IntoView
+    where
+        IV: IntoView,
+        EV: IntoView,
+    {
+        self.render_ok_with_fallback(|| "Loading...", view, error_view)
+    }
+
+    /// Renders the data like [`render_ok`](QueryResult::render_ok), showing the given fallback until the data is first loaded.
+    /// See [`render_with_fallback`](QueryResult::render_with_fallback).
+    pub fn render_ok_with_fallback<IV, EV>(
+        &self,
+        fallback: impl Into<ViewFn>,
+        view: impl Fn(T) -> IV + 'static,
+        error_view: impl Fn(E) -> EV + 'static,
+    ) -> impl IntoView
+    where
+        IV: IntoView,
+        EV: IntoView,
+    {
+        self.render_with_fallback(fallback, move |result| match result {
+            Ok(data) => view(data).into_view(),
+            Err(error) => error_view(error).into_view(),
+        })
+    }
+}
+
+/// Targeted edits of list data, for optimistic updates.
+///
+/// Each edit applies to the query's current key, like [`set_data`](QueryResult::set_data), and only if the query has data.
+/// Observers are only notified if the list changed.
+/// Results derived with [`map`](QueryResult::map) or [`zip`](QueryResult::zip) aren't bound to a key, so edits do nothing for them.
+///
+/// Example:
+/// ```no_run
+/// use leptos::*;
+/// use leptos_query::*;
+/// # #[derive(Clone, serde::Deserialize, serde::Serialize)]
+/// # struct Todo { id: u32, done: bool }
+/// # async fn get_todos() -> Vec<Todo> { todo!() }
+///
+/// let todos = use_query(|| (), |_| get_todos(), QueryOptions::default());
+///
+/// todos.append_item(Todo { id: 3, done: false });
+/// todos.update_where(|todo| todo.id == 3, |todo| todo.done = true);
+/// todos.remove_where(|todo| todo.done);
+/// ```
+impl<T, R> QueryResult<Vec<T>, R>
+where
+    T: Clone + 'static,
+    R: RefetchFn,
+{
+    /// Appends an item to the end of the list.
+    pub fn append_item(&self, item: T) {
+        if let Some(handle) = &self.handle {
+            handle.update_data(Box::new(append_item(item)));
+        }
+    }
+
+    /// Removes the items matching the predicate. Returns the number of removed items.
+    pub fn remove_where(&self, predicate: impl Fn(&T) -> bool + 'static) -> usize {
+        let removed = Rc::new(Cell::new(0));
+        if let Some(handle) = &self.handle {
+            handle.update_data(Box::new(remove_where(predicate, removed.clone())));
+        }
+        removed.get()
+    }
+
+    /// Updates the items matching the predicate in place. Returns the number of updated items.
+    pub fn update_where(
+        &self,
+        predicate: impl Fn(&T) -> bool + 'static,
+        update: impl FnMut(&mut T) + 'static,
+    ) -> usize {
+        let updated = Rc::new(Cell::new(0));
+        if let Some(handle) = &self.handle {
+            handle.update_data(Box::new(update_where(predicate, update, updated.clone())));
+        }
+        updated.get()
+    }
+}
+
+pub(crate) fn append_item<T: Clone>(item: T) -> impl FnOnce(Option<&Vec<T>>) -> Option<Vec<T>> {
+    move |items| {
+        let mut items = items?.clone();
+        items.push(item);
+        Some(items)
+    }
+}
+
+// Bails out of the update if nothing matched, so observers aren't notified.
+pub(crate) fn remove_where<T: Clone>(
+    predicate: impl Fn(&T) -> bool,
+    removed: Rc<Cell<usize>>,
+) -> impl FnOnce(Option<&Vec<T>>) -> Option<Vec<T>> {
+    move |items| {
+        let items = items?;
+        let kept: Vec<T> = items
+            .iter()
+            .filter(|item| !predicate(item))
+            .cloned()
+            .collect();
+        removed.set(items.len() - kept.len());
+        (removed.get() > 0).then_some(kept)
+    }
+}
+
+pub(crate) fn update_where<T: Clone>(
+    predicate: impl Fn(&T) -> bool,
+    mut update: impl FnMut(&mut T),
+    updated: Rc<Cell<usize>>,
+) -> impl FnOnce(Option<&Vec<T>>) -> Option<Vec<T>> {
+    move |items| {
+        let mut items = items?.clone();
+        for item in items.iter_mut().filter(|item| predicate(item)) {
+            update(item);
+            updated.set(updated.get() + 1);
+        }
+        (updated.get() > 0).then_some(items)
+    }
+}
+
+fn map_state<V, U>(state: &QueryState<V>, func: impl Fn(&V) -> U) -> QueryState<U> {
+    let map_data = |data: &QueryData<V>| QueryData {
+        data: func(&data.data),
+        updated_at: data.updated_at,
+    };
+    match state {
+        QueryState::Created => QueryState::Created,
+        QueryState::Idle => QueryState::Idle,
+        QueryState::Loading => QueryState::Loading,
+        QueryState::Fetching(data) => QueryState::Fetching(map_data(data)),
+        QueryState::Loaded(data) => QueryState::Loaded(map_data(data)),
+        QueryState::Invalid(data) => QueryState::Invalid(map_data(data)),
+    }
+}
+
+fn zip_state<V: Clone, W: Clone>(a: &QueryState<V>, b: &QueryState<W>) -> QueryState<(V, W)> {
+    match (a.query_data(), b.query_data()) {
+        (Some(a_data), Some(b_data)) => {
+            let data = QueryData {
+                data: (a_data.data.clone(), b_data.data.clone()),
+                updated_at: a_data.updated_at.min(b_data.updated_at),
+            };
+            match (a, b) {
+                (QueryState::Fetching(_), _) | (_, QueryState::Fetching(_)) => {
+                    QueryState::Fetching(data)
+                }
+                (QueryState::Invalid(_), _) | (_, QueryState::Invalid(_)) => {
+                    QueryState::Invalid(data)
+                }
+                _ => QueryState::Loaded(data),
+            }
+        }
+        _ => match (a, b) {
+            (QueryState::Loading | QueryState::Fetching(_), _)
+            | (_, QueryState::Loading | QueryState::Fetching(_)) => QueryState::Loading,
+            (QueryState::Idle, _) | (_, QueryState::Idle) => QueryState::Idle,
+            _ => QueryState::Created,
+        },
+    }
+}
+
+struct Suspended<V> {
+    value: Option<V>,
+    waker: Option<Waker>,
+}
+
+type DataUpdater<V> = Box<dyn FnOnce(Option<&V>) -> Option<V>>;
+
+// Edits the cache entry of a result's current key, without exposing the key's type.
+pub(crate) trait QueryHandle<V> {
+    fn with_data(&self, f: &mut dyn FnMut(Option<&V>));
+    fn update_data(&self, updater: DataUpdater<V>);
+    fn invalidate(&self) -> bool;
+    fn remove(&self) -> bool;
+    fn cancel(&self) -> bool;
+    fn refetch_quietly(&self);
+}
+
+struct CachedQuery<K: 'static, V: 'static> {
+    client: QueryClient,
+    query: Signal<Query<K, V>>,
+    refetch: Rc<dyn Fn()>,
+}
+
+impl<K, V> QueryHandle<V> for CachedQuery<K, V>
+where
+    K: Hash + Eq + Clone + 'static,
+    V: Clone + 'static,
+{
+    fn with_data(&self, f: &mut dyn FnMut(Option<&V>)) {
+        self.query.with(|query| {
+            if cfg!(debug_assertions) && self.client.unread_refetch_after.is_some() {
+                query.reads.record();
+            }
+            query.state.with(|state| f(state.data()))
+        })
+    }
+
+    fn update_data(&self, updater: DataUpdater<V>) {
+        let key = self.query.with_untracked(|query| query.key.clone());
+        self.client.set_query_data::<K, V>(key, updater);
+    }
+
+    fn invalidate(&self) -> bool {
+        self.query.with_untracked(|query| query.mark_invalid())
+    }
+
+    fn remove(&self) -> bool {
+        self.query
+            .with_untracked(|query| self.client.evict_and_notify::<K, V>(query))
+            .is_some()
+    }
+
+    fn cancel(&self) -> bool {
+        self.query.with_untracked(|query| query.cancel())
+    }
+
+    fn refetch_quietly(&self) {
+        self.query
+            .with_untracked(|query| query.quiet_next_fetch.set(true));
+        (self.refetch)();
+        // Cleared even if the executor didn't run, such as where the query isn't fetched.
+        self.query
+            .with_untracked(|query| query.quiet_next_fetch.set(false));
+    }
+}
+
+// Results that only exist once resolved, such as dependent queries.
+impl<V, R> QueryHandle<V> for RwSignal<Option<QueryResult<V, R>>>
+where
+    V: Clone + 'static,
+    R: RefetchFn + 'static,
+{
+    fn with_data(&self, f: &mut dyn FnMut(Option<&V>)) {
+        self.with(|r| match r {
+            Some(r) => r.with_data(&mut *f),
+            None => f(None),
+        })
+    }
+
+    fn update_data(&self, updater: DataUpdater<V>) {
+        if let Some(handle) = self.with_untracked(|r| r.as_ref().and_then(|r| r.handle.clone())) {
+            handle.update_data(updater);
+        }
+    }
+
+    fn invalidate(&self) -> bool {
+        self.with_untracked(|r| r.as_ref().and_then(|r| r.handle.clone()))
+            .is_some_and(|handle| handle.invalidate())
+    }
+
+    fn remove(&self) -> bool {
+        self.with_untracked(|r| r.as_ref().and_then(|r| r.handle.clone()))
+            .is_some_and(|handle| handle.remove())
+    }
+
+    fn cancel(&self) -> bool {
+        self.with_untracked(|r| r.as_ref().and_then(|r| r.handle.clone()))
+            .is_some_and(|handle| handle.cancel())
+    }
+
+    fn refetch_quietly(&self) {
+        if let Some(handle) = self.with_untracked(|r| r.as_ref().and_then(|r| r.handle.clone())) {
+            handle.refetch_quietly();
+        }
+    }
 }
 
 /// Convenience Trait alias for a Query Result's refetch function.
 pub trait RefetchFn: Fn() + Clone {}
 impl<R: Fn() + Clone> RefetchFn for R {}
 
-pub(crate) fn create_query_result<K: Clone, V: Clone>(
+pub(crate) fn create_query_result<K, V>(
     query: Signal<Query<K, V>>,
     data: Signal<Option<V>>,
-    executor: impl Fn() + Clone,
-) -> QueryResult<V, impl RefetchFn> {
+    executor: impl Fn() + Clone + 'static,
+) -> QueryResult<V, impl RefetchFn>
+where
+    K: Hash + Eq + Clone + 'static,
+    V: Clone + 'static,
+{
     let state = Signal::derive(move || query.get().state.get());
 
-    let is_loading = Signal::derive(move || matches!(state.get(), QueryState::Loading));
-    let is_fetching = Signal::derive(move || {
-        matches!(state.get(), QueryState::Loading | QueryState::Fetching(_))
+    // Record reads of the data, see `QueryClientBuilder::warn_on_unread_refetches`.
+    let data = if cfg!(debug_assertions) && use_query_client().unread_refetch_after.is_some() {
+        Signal::derive(move || {
+            query.with_untracked(|query| query.reads.record());
+            data.get()
+        })
+    } else {
+        data
+    };
+
+    // Flags borrow the query's state, rather than cloning its data on every change.
+    let is_loading = state_flag(query, |state| matches!(state, QueryState::Loading));
+    let is_initial_loading = state_flag(query, |state| {
+        matches!(state, QueryState::Created | QueryState::Loading)
+    });
+    let is_fetching = state_flag(query, |state| {
+        matches!(state, QueryState::Loading | QueryState::Fetching(_))
     });
-    let is_invalid = Signal::derive(move || matches!(state.get(), QueryState::Invalid(_)));
+    let is_invalid = state_flag(query, |state| matches!(state, QueryState::Invalid(_)));
+    let updated_at =
+        Signal::derive(move || query.with(|query| query.state.with(|state| state.updated_at())));
+    let is_paused = Signal::derive(move || query.get().paused.get());
+    let progress = Signal::derive(move || query.get().progress.get());
 
     // Make stale time.
     let stale_time = Signal::derive(move || query.get().stale_time.get());
-    let is_stale = make_is_stale(state, stale_time);
+    let is_stale = make_is_stale(updated_at, stale_time);
+    // Marked stale when the app regained focus, see [`QueryClientBuilder::mark_stale_on_focus`](crate::QueryClientBuilder::mark_stale_on_focus).
+    let is_marked_stale = Signal::derive(move || {
+        let query = query.get();
+        let marked_stale = query.marked_stale.get();
+        marked_stale.is_some() && marked_stale == updated_at.get()
+    });
+    let is_stale = Signal::derive(move || is_stale.get() || is_marked_stale.get());
+
+    // Explicit refetches are never skipped as duplicates of a recent fetch.
+    let refetch = move || {
+        query.with_untracked(|query| query.refetch_requested.set(true));
+        executor();
+        query.with_untracked(|query| query.refetch_requested.set(false));
+    };
 
     QueryResult {
         data,
         state,
         is_loading,
+        is_initial_loading,
         is_fetching,
         is_stale,
         is_invalid,
-        refetch: executor,
+        is_paused,
+        progress,
+        refetch: refetch.clone(),
+        resource: None,
+        handle: Some(Rc::new(CachedQuery {
+            client: use_query_client(),
+            query,
+            refetch: Rc::new(refetch),
+        })),
+        updated_at,
     }
 }
 
-fn make_is_stale<V: Clone>(
-    state: Signal<QueryState<V>>,
+fn state_flag<K, V>(
+    query: Signal<Query<K, V>>,
+    flag: impl Fn(&QueryState<V>) -> bool + 'static,
+) -> Signal<bool>
+where
+    K: Clone + 'static,
+    V: Clone + 'static,
+{
+    Signal::derive(move || query.with(|query| query.state.with(|state| flag(state))))
+}
+
+fn make_is_stale(
+    updated_at: Signal<Option<Instant>>,
     stale_time: Signal<Option<Duration>>,
 ) -> Signal<bool> {
     let (stale, set_stale) = create_signal(false);
+    let scheduler = use_query_client().scheduler;
 
-    let _ = use_timeout(move || {
-        match maybe_time_until_stale(state.get().updated_at(), stale_time.get()) {
+    let _ = use_timeout(
+        move || match maybe_time_until_stale(updated_at.get(), stale_time.get()) {
             Some(Duration::ZERO) => {
                 set_stale.set(true);
                 None
             }
             Some(timeout) => {
                 set_stale.set(false);
-                set_timeout_with_handle(
+                Some(scheduler.schedule(
                     move || {
                         set_stale.set(true);
                     },
                     timeout,
-                )
-                .ok()
+                ))
             }
             None => None,
-        }
-    });
+        },
+    );
 
     stale.into()
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        provide_query_client, suppress_query_load, use_query, QueryOptions, ResourceData, SsrPolicy,
+    };
+
+    #[test]
+    fn use_query_results_are_backed_by_a_resource() {
+        let _ = create_runtime();
+
+        provide_query_client();
+        let client = use_query_client();
+
+        let query = use_query(
+            || 0_u32,
+            |id| async move { id + 1 },
+            QueryOptions::default(),
+        );
+        let resource = query.resource().expect("created by use_query");
+        create_isomorphic_effect(move |_| query.data.track());
+
+        assert!(!resource.loading().get_untracked());
+        assert_eq!(
+            Some(1),
+            untrack(|| resource.get()).and_then(ResourceData::into_data)
+        );
+
+        let fetched = client.fetch_query(|| 0_u32, |id| async move { id + 1 }, true);
+        assert!(fetched.resource().is_none());
+    }
+
+    #[test]
+    fn time_since_update_advances() {
+        let _ = create_runtime();
+
+        provide_query_client();
+        Instant::freeze_now();
+
+        let query = use_query(|| 0_u32, |id| async move { id }, QueryOptions::default());
+        let since_update = query.time_since_update(Duration::from_secs(1));
+        assert_eq!(None, since_update.get_untracked());
+
+        create_isomorphic_effect(move |_| query.data.track());
+        assert_eq!(Some(Duration::ZERO), since_update.get_untracked());
+
+        Instant::advance_now(Duration::from_secs(5));
+        assert_eq!(Some(Duration::from_secs(5)), since_update.get_untracked());
+
+        // Mapped results share the update time.
+        let mapped = query
+            .map(|id| id + 1)
+            .time_since_update(Duration::from_secs(1));
+        assert_eq!(Some(Duration::from_secs(5)), mapped.get_untracked());
+    }
+
+    // Counts its clones.
+    #[derive(PartialEq)]
+    struct Counted(u32, Rc<Cell<usize>>);
+
+    impl Clone for Counted {
+        fn clone(&self) -> Self {
+            self.1.set(self.1.get() + 1);
+            Counted(self.0, self.1.clone())
+        }
+    }
+
+    #[test]
+    fn with_data_borrows_without_cloning() {
+        let _ = create_runtime();
+
+        provide_query_client();
+        let client = use_query_client();
+
+        let clones = Rc::new(Cell::new(0));
+        let fetcher = {
+            let clones = clones.clone();
+            move |id: u32| {
+                let clones = clones.clone();
+                async move { Counted(id + 1, clones) }
+            }
+        };
+        let result = client.fetch_query(|| 0_u32, fetcher, true);
+
+        let before = clones.get();
+        assert_eq!(Some(1), result.with_data(|data| data.map(|data| data.0)));
+        assert_eq!(before, clones.get());
+    }
+
+    #[test]
+    fn data_memo_notifies_only_on_changes() {
+        let _ = create_runtime();
+
+        provide_query_client();
+        let client = use_query_client();
+
+        let result = client.fetch_query(|| 0_u32, |_| async { 1_u32 }, true);
+        let memo = result.data_memo();
+        let runs = Rc::new(Cell::new(0));
+        create_isomorphic_effect({
+            let runs = runs.clone();
+            move |_| {
+                memo.track();
+                runs.set(runs.get() + 1);
+            }
+        });
+        assert_eq!(Some(1), memo.get_untracked());
+        assert_eq!(1, runs.get());
+
+        result.set_data(1);
+        assert_eq!(1, runs.get());
+
+        result.set_data(2);
+        assert_eq!(Some(2), memo.get_untracked());
+        assert_eq!(2, runs.get());
+    }
+
+    #[test]
+    fn renders_data_or_the_fallback() {
+        let html = leptos::ssr::render_to_string(|| {
+            provide_query_client();
+            let client = use_query_client();
+            client.set_query_data::<u32, Result<u32, String>>(1, |_| Some(Err("gone".into())));
+
+            // Not fetched where the code runs, so it has no data.
+            let not_here = if cfg!(feature = "ssr") {
+                SsrPolicy::ClientOnly
+            } else {
+                SsrPolicy::ServerOnly
+            };
+            let fetcher = |_| async { Ok::<u32, String>(0) };
+            let pending = client.fetch_query(|| 0_u32, fetcher, not_here);
+            let failed = client.fetch_query(|| 1_u32, fetcher, not_here);
+            view! {
+                <div>
+                    {pending.render_ok_with_fallback(
+                        || "Finding the monkey",
+                        |id| view! { <b>{id}</b> },
+                        |error| view! { <i>{error}</i> },
+                    )}
+                    {failed.render_ok(|id| view! { <b>{id}</b> }, |error| view! { <i>{error}</i> })}
+                </div>
+            }
+        });
+
+        assert!(html.contains("Finding the monkey"));
+        assert!(html.contains(">gone</i>"));
+    }
+
+    #[test]
+    fn suspend_resolves_with_fetched_data() {
+        let _ = create_runtime();
+
+        provide_query_client();
+        let client = use_query_client();
+
+        let result = client.fetch_query(|| 0_u32, |id| async move { id + 1 }, true);
+
+        let resolved = Rc::new(Cell::new(None));
+        spawn_local({
+            let resolved = resolved.clone();
+            let suspended = result.suspend();
+            async move { resolved.set(Some(suspended.await)) }
+        });
+
+        assert_eq!(Some(1), resolved.get());
+    }
+
+    #[test]
+    fn query_results_can_be_mapped_and_zipped() {
+        let _ = create_runtime();
+
+        provide_query_client();
+        let client = use_query_client();
+
+        let name = client.fetch_query(|| 0_u32, |_| async { "monkey".to_string() }, true);
+        let age = client.fetch_query(|| 0_u32, |id| async move { id + 3 }, true);
+
+        let length = name.map(|name| name.len());
+        assert_eq!(Some(6), length.data.get_untracked());
+
+        let zipped = length.zip(&age);
+        assert_eq!(Some((6, 3)), zipped.data.get_untracked());
+        assert!(matches!(
+            zipped.state.get_untracked(),
+            QueryState::Loaded(QueryData { data: (6, 3), .. })
+        ));
+        assert!(!zipped.is_loading.get_untracked());
+    }
+
+    #[test]
+    fn result_accessors_split_ok_and_err() {
+        let _ = create_runtime();
+
+        provide_query_client();
+        let client = use_query_client();
+
+        let ok = client.fetch_query(|| 0_u32, |_| async { Ok::<u32, String>(1) }, true);
+        let err = client.fetch_query(
+            || 1_u32,
+            |_| async { Err::<u32, String>("gone".into()) },
+            true,
+        );
+
+        assert_eq!(Some(1), ok.data_ok().get_untracked());
+        assert_eq!(None, ok.error().get_untracked());
+        assert_eq!(Some("gone".to_string()), err.error().get_untracked());
+        assert_eq!(0, err.unwrap_or(0).get_untracked());
+    }
+
+    #[test]
+    fn query_result_edits_its_cache_entry() {
+        let _ = create_runtime();
+
+        provide_query_client();
+        let client = use_query_client();
+
+        let result = client.fetch_query(|| 0_u32, |_| async { 1_u32 }, true);
+        assert_eq!(Some(1), result.data.get_untracked());
+
+        result.set_data(5);
+        assert_eq!(Some(5), result.data.get_untracked());
+        assert_eq!(
+            Some(5),
+            client
+                .clone()
+                .get_query_state::<u32, u32>(|| 0)
+                .get_untracked()
+                .and_then(|state| state.data().cloned())
+        );
+
+        let mapped = result.map(|data| data + 1);
+        mapped.set_data(10);
+        assert!(!mapped.invalidate());
+        assert_eq!(Some(5), result.data.get_untracked());
+
+        assert!(result.invalidate());
+        assert!(result.remove());
+        assert!(!result.remove());
+        assert_eq!(0, client.size().get_untracked());
+    }
+
+    #[test]
+    fn initial_loading_until_first_data() {
+        let _ = create_runtime();
+
+        provide_query_client();
+        let client = use_query_client();
+
+        suppress_query_load(true);
+        let pending = client.fetch_query(|| 0_u32, |_| async { 1_u32 }, true);
+        suppress_query_load(false);
+        assert!(pending.is_initial_loading.get_untracked());
+        assert!(!pending.is_loading.get_untracked());
+
+        let loaded = client.fetch_query(|| 1_u32, |_| async { 1_u32 }, true);
+        assert!(!loaded.is_initial_loading.get_untracked());
+    }
+}
@@ -4,7 +4,10 @@ use crate::Instant;
 ///
 /// Each variant in the enum corresponds to a particular state of a query in its lifecycle,
 /// starting from creation and covering all possible transitions up to invalidation.
+///
+/// More states may be added in the future, so matches need a wildcard arm.
 #[derive(Clone, PartialEq, Eq)]
+#[non_exhaustive]
 pub enum QueryState<V> {
     /// The initial state of a Query upon its creation.
     ///
@@ -13,6 +16,13 @@ pub enum QueryState<V> {
     /// waiting to begin its first fetch operation.
     Created,
 
+    /// A query that is prevented from fetching, such as a [dependent query](crate::use_dependent_query) whose key isn't resolved yet,
+    /// or a query whose [`SsrPolicy`](crate::SsrPolicy) doesn't fetch it where the code is running.
+    ///
+    /// Unlike [`Created`](Self::Created), which is about to fetch, an idle query has no data and isn't going to fetch until it is allowed to.
+    /// It's only fetched by an observer that is allowed to, and [`QueryClient::refetch_query_with`](crate::QueryClient::refetch_query_with) ignores it.
+    Idle,
+
     /// Query is fetching for the first time.
     ///
     /// In this state, the query has started its first data fetching process. It is actively communicating
@@ -41,7 +51,7 @@ impl<V> QueryState<V> {
     /// Returns the QueryData for the current QueryState, if present.
     pub fn query_data(&self) -> Option<&QueryData<V>> {
         match self {
-            QueryState::Loading | QueryState::Created => None,
+            QueryState::Loading | QueryState::Created | QueryState::Idle => None,
             QueryState::Fetching(data) | QueryState::Loaded(data) | QueryState::Invalid(data) => {
                 Some(data)
             }
@@ -66,6 +76,7 @@ where
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
             Self::Created => write!(f, "Created"),
+            Self::Idle => write!(f, "Idle"),
             Self::Loading => write!(f, "Loading"),
             Self::Fetching(arg0) => f.debug_tuple("Fetching").field(arg0).finish(),
             Self::Loaded(arg0) => f.debug_tuple("Loaded").field(arg0).finish(),
@@ -76,6 +87,7 @@ where
 
 /// The latest data for a Query.
 #[derive(Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct QueryData<V> {
     /// The Data.
     pub data: V,
@@ -0,0 +1,183 @@
+use leptos::on_cleanup;
+use std::{
+    cell::{Cell, RefCell},
+    rc::Rc,
+};
+
+use crate::QueryClient;
+
+/// The parameters of a path matched by a route prefetch.
+/// See [`QueryClient::register_route_prefetch`](crate::QueryClient::register_route_prefetch).
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct RouteParams {
+    params: Vec<(String, String)>,
+}
+
+impl RouteParams {
+    /// Returns the value of the `:name` segment, or of the `*name` wildcard.
+    pub fn get(&self, name: &str) -> Option<&str> {
+        get_param(&self.params, name)
+    }
+}
+
+impl MatchedParams for RouteParams {
+    fn from_matched(params: Vec<(String, String)>) -> Self {
+        Self { params }
+    }
+}
+
+// Parameters matched by the segments of a path pattern, such as route or tag parameters.
+pub(crate) trait MatchedParams {
+    fn from_matched(params: Vec<(String, String)>) -> Self;
+}
+
+pub(crate) fn get_param<'a>(params: &'a [(String, String)], name: &str) -> Option<&'a str> {
+    params
+        .iter()
+        .find(|(param, _)| param == name)
+        .map(|(_, value)| value.as_str())
+}
+
+type Handler<P> = Rc<dyn Fn(&QueryClient, &P)>;
+
+// A handler with the id it was registered with, and its pattern.
+type Registration<P> = (u64, String, Handler<P>);
+
+// Handlers registered per path pattern, such as route prefetches & tag invalidations.
+pub(crate) struct PathHandlers<P> {
+    next_id: Rc<Cell<u64>>,
+    handlers: Rc<RefCell<Vec<Registration<P>>>>,
+}
+
+impl<P> Clone for PathHandlers<P> {
+    fn clone(&self) -> Self {
+        Self {
+            next_id: self.next_id.clone(),
+            handlers: self.handlers.clone(),
+        }
+    }
+}
+
+impl<P> Default for PathHandlers<P> {
+    fn default() -> Self {
+        Self {
+            next_id: Rc::default(),
+            handlers: Rc::default(),
+        }
+    }
+}
+
+impl<P: MatchedParams + 'static> PathHandlers<P> {
+    // The handler is unregistered once the current scope is disposed, so components don't register it again on every mount.
+    pub(crate) fn register(&self, pattern: String, handler: Handler<P>) {
+        let id = self.next_id.get();
+        self.next_id.set(id + 1);
+        self.handlers.borrow_mut().push((id, pattern, handler));
+
+        let handlers = self.handlers.clone();
+        on_cleanup(move || handlers.borrow_mut().retain(|(handler, ..)| *handler != id));
+    }
+
+    // Handlers are collected first, so they can register more handlers.
+    pub(crate) fn matching(&self, path: &str) -> Vec<(Handler<P>, P)> {
+        self.handlers
+            .borrow()
+            .iter()
+            .filter_map(|(_, pattern, handler)| {
+                match_path(pattern, path).map(|params| (handler.clone(), P::from_matched(params)))
+            })
+            .collect()
+    }
+}
+
+// Matches a path against a route, in the style of `leptos_router`:
+// `:name` matches a single segment, and a trailing `*name` matches the rest of the path.
+// Query strings & fragments of the path are ignored.
+fn match_path(route: &str, path: &str) -> Option<Vec<(String, String)>> {
+    let path = path.split(['?', '#']).next().unwrap_or_default();
+    let mut path_segments = path.split('/').filter(|s| !s.is_empty());
+    let mut params = Vec::new();
+
+    for segment in route.split('/').filter(|s| !s.is_empty()) {
+        if let Some(name) = segment.strip_prefix('*') {
+            let rest = path_segments.collect::<Vec<_>>().join("/");
+            params.push((name.to_string(), rest));
+            return Some(params);
+        }
+        let value = path_segments.next()?;
+        match segment.strip_prefix(':') {
+            Some(name) => params.push((name.to_string(), value.to_string())),
+            None if segment == value => (),
+            None => return None,
+        }
+    }
+
+    match path_segments.next() {
+        Some(_) => None,
+        None => Some(params),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{provide_query_client, use_query_client};
+    use leptos::*;
+
+    #[test]
+    fn matches_static_params_and_wildcards() {
+        assert!(match_path("/", "/").is_some());
+        assert!(match_path("/posts", "/posts?page=2").is_some());
+        assert!(match_path("/posts", "/posts/1").is_none());
+        assert!(match_path("/posts/:id", "/users/1").is_none());
+
+        let params = match_path("/posts/:id", "/posts/1/").unwrap();
+        assert_eq!(Some("1"), get_param(&params, "id"));
+
+        let params = match_path("/files/*path", "/files/a/b.txt").unwrap();
+        assert_eq!(Some("a/b.txt"), get_param(&params, "path"));
+    }
+
+    #[test]
+    fn prefetches_registered_routes() {
+        let _ = create_runtime();
+
+        provide_query_client();
+        let client = use_query_client();
+
+        client
+            .register_route_prefetch("/posts/:id", |client, params| {
+                let id = params.get("id").and_then(|id| id.parse::<u32>().ok());
+                if let Some(id) = id {
+                    client.prefetch_query(move || id, |id| async move { id * 10 }, true);
+                }
+            })
+            .register_route_prefetch("/posts/*rest", |_, _| ());
+
+        assert_eq!(2, client.prefetch_route("/posts/2?tab=comments"));
+        assert_eq!(0, client.prefetch_route("/users/2"));
+
+        let state = client.clone().get_query_state::<u32, u32>(|| 2);
+        assert_eq!(
+            Some(20),
+            state.get_untracked().and_then(|s| s.data().cloned())
+        );
+    }
+
+    #[test]
+    fn unregisters_route_prefetches_with_their_scope() {
+        let _ = create_runtime();
+
+        provide_query_client();
+        let client = use_query_client();
+
+        let ((), disposer) = as_child_of_current_owner(|client: QueryClient| {
+            client.register_route_prefetch("/posts/:id", |_, _| ());
+        })(client.clone());
+        assert_eq!(1, client.prefetch_route("/posts/1"));
+
+        // Such as a component unmounting.
+        drop(disposer);
+        assert_eq!(0, client.prefetch_route("/posts/1"));
+    }
+}
@@ -0,0 +1,170 @@
+use leptos::{leptos_dom::helpers::TimeoutHandle, *};
+use std::{
+    cell::RefCell,
+    collections::BTreeMap,
+    rc::{Rc, Weak},
+    time::Duration,
+};
+
+use crate::{Instant, INFINITE_TIME};
+
+// Deadline & insertion order, so tasks with the same deadline run in the order they were scheduled.
+type TaskKey = (Duration, u64);
+
+// Browser timeouts take at most `i32::MAX` milliseconds (about 24.8 days). Later deadlines are armed in steps.
+const MAX_TIMEOUT: Duration = Duration::from_millis(i32::MAX as u64);
+
+/// Runs all of a client's timed work (staleness, refetch intervals, cache cleanup) from a single timer.
+///
+/// Only one browser timeout is armed at a time, for the earliest deadline.
+#[derive(Clone, Default)]
+pub(crate) struct Scheduler {
+    inner: Rc<RefCell<SchedulerInner>>,
+}
+
+#[derive(Default)]
+struct SchedulerInner {
+    next_id: u64,
+    tasks: BTreeMap<TaskKey, Box<dyn FnOnce()>>,
+    armed: Option<(Duration, TimeoutHandle)>,
+}
+
+/// Cancels a scheduled task.
+#[derive(Clone)]
+pub(crate) struct TimerHandle {
+    inner: Weak<RefCell<SchedulerInner>>,
+    key: TaskKey,
+}
+
+impl TimerHandle {
+    pub(crate) fn clear(&self) {
+        if let Some(inner) = self.inner.upgrade() {
+            inner.borrow_mut().tasks.remove(&self.key);
+        }
+    }
+}
+
+impl Scheduler {
+    /// Runs the task once the delay has passed. A task delayed by [`INFINITE_TIME`], or past the end of time, never runs, so it isn't stored.
+    pub(crate) fn schedule(&self, task: impl FnOnce() + 'static, delay: Duration) -> TimerHandle {
+        let key = {
+            let mut inner = self.inner.borrow_mut();
+            let key = (Instant::now().0.saturating_add(delay), inner.next_id);
+            inner.next_id += 1;
+            if delay != INFINITE_TIME && key.0 != Duration::MAX {
+                inner.tasks.insert(key, Box::new(task));
+            }
+            key
+        };
+        self.arm();
+
+        TimerHandle {
+            inner: Rc::downgrade(&self.inner),
+            key,
+        }
+    }
+
+    /// Runs every task that is due at the given time, in deadline order.
+    pub(crate) fn run_due(&self, now: Instant) {
+        loop {
+            let task = {
+                let mut inner = self.inner.borrow_mut();
+                match inner.tasks.first_key_value() {
+                    Some(((deadline, _), _)) if *deadline <= now.0 => {
+                        inner.tasks.pop_first().map(|(_, task)| task)
+                    }
+                    _ => None,
+                }
+            };
+            // Tasks may schedule or clear other tasks.
+            match task {
+                Some(task) => task(),
+                None => break,
+            }
+        }
+        self.arm();
+    }
+
+    // Ensures the timer is set for the earliest deadline.
+    fn arm(&self) {
+        let mut inner = self.inner.borrow_mut();
+        let earliest = inner.tasks.keys().next().map(|(deadline, _)| *deadline);
+        if earliest.is_some() && inner.armed.map(|(deadline, _)| deadline) == earliest {
+            return;
+        }
+
+        if let Some((_, handle)) = inner.armed.take() {
+            handle.clear();
+        }
+
+        // Outside the browser there are no timers, and tasks only run through `run_due`.
+        if let (Some(deadline), true) = (earliest, cfg!(target_arch = "wasm32")) {
+            let scheduler = self.clone();
+            let delay = timeout_delay(deadline, Instant::now());
+            inner.armed = set_timeout_with_handle(
+                move || {
+                    scheduler.inner.borrow_mut().armed = None;
+                    scheduler.run_due(Instant::now());
+                },
+                delay,
+            )
+            .ok()
+            .map(|handle| (deadline, handle));
+        }
+    }
+}
+
+// The delay to arm the timer with. Once a clamped timer fires, the scheduler arms it again for the rest.
+fn timeout_delay(deadline: Duration, now: Instant) -> Duration {
+    deadline.saturating_sub(now.0).min(MAX_TIMEOUT)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn runs_due_tasks_in_deadline_order() {
+        let scheduler = Scheduler::default();
+        let ran = Rc::new(RefCell::new(Vec::new()));
+
+        let task = |name: &'static str| {
+            let ran = ran.clone();
+            move || ran.borrow_mut().push(name)
+        };
+
+        scheduler.schedule(task("late"), Duration::from_secs(20));
+        scheduler.schedule(task("early"), Duration::from_secs(10));
+        let cancelled = scheduler.schedule(task("cancelled"), Duration::from_secs(15));
+        cancelled.clear();
+
+        scheduler.run_due(Instant::now());
+        assert!(ran.borrow().is_empty());
+
+        scheduler.run_due(Instant(Instant::now().0 + Duration::from_secs(30)));
+        assert_eq!(vec!["early", "late"], *ran.borrow());
+    }
+
+    #[test]
+    fn never_stores_infinite_tasks() {
+        let scheduler = Scheduler::default();
+
+        scheduler.schedule(|| panic!("never runs"), INFINITE_TIME);
+        assert!(scheduler.inner.borrow().tasks.is_empty());
+
+        scheduler.run_due(Instant(Duration::MAX));
+    }
+
+    #[test]
+    fn clamps_timeouts_to_what_browsers_accept() {
+        let now = Instant::now();
+        let soon = now.0 + Duration::from_secs(10);
+        assert_eq!(Duration::from_secs(10), timeout_delay(soon, now));
+        assert_eq!(Duration::ZERO, timeout_delay(now.0, now));
+
+        let in_a_year = now.0 + Duration::from_secs(60 * 60 * 24 * 365);
+        assert_eq!(MAX_TIMEOUT, timeout_delay(in_a_year, now));
+        assert_eq!(MAX_TIMEOUT, timeout_delay(Duration::MAX, now));
+        assert!(i32::try_from(MAX_TIMEOUT.as_millis()).is_ok());
+    }
+}
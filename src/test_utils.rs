@@ -0,0 +1,161 @@
+//! Helpers for integration tests of query logic, such as browser tests with `wasm-bindgen-test`.
+//!
+//! Enabled with the `test_utils` feature. Intended for dev-dependencies only.
+//!
+//! Example:
+//! ```ignore
+//! use leptos::*;
+//! use leptos_query::{test_utils::*, *};
+//! use wasm_bindgen_test::*;
+//!
+//! wasm_bindgen_test_configure!(run_in_browser);
+//!
+//! #[wasm_bindgen_test]
+//! async fn shows_the_monkey() {
+//!     mount_with_client(QueryClientBuilder::new(), || view! { <MonkeyName id=1/> });
+//!
+//!     let client = use_query_client();
+//!     settled(&client).await;
+//!     assert_query_data::<u32, Monkey>(&client, 1, Some(Monkey::new("George")));
+//! }
+//! ```
+
+use leptos::*;
+use std::{borrow::Borrow, fmt::Debug, hash::Hash};
+
+use crate::{provide_query_client_with, QueryClient, QueryClientBuilder};
+
+/// Mounts the view to the `<body>`, below a [`QueryClient`] configured with the given builder.
+///
+/// The client can be retrieved from within the test with [`use_query_client`](crate::use_query_client).
+/// Mounting requires a browser.
+pub fn mount_with_client<F, N>(builder: QueryClientBuilder, view: F)
+where
+    F: FnOnce() -> N + 'static,
+    N: IntoView,
+{
+    provide_query_client_with(builder);
+    let client = crate::use_query_client();
+    mount_to_body(move || {
+        provide_context(client);
+        view()
+    });
+}
+
+/// Lets pending timers, microtasks, and spawned fetches run, before resuming the test.
+pub async fn flush() {
+    cfg_if::cfg_if! {
+        if #[cfg(feature = "hydrate")] {
+            gloo_timers::future::sleep(std::time::Duration::ZERO).await;
+        } else if #[cfg(feature = "ssr")] {
+            tokio::time::sleep(std::time::Duration::ZERO).await;
+        } else {
+            yield_now().await;
+        }
+    }
+}
+
+/// Waits until none of the client's queries is loading or fetching.
+///
+/// Fetches waiting for the network, see [`OnlineManager`](crate::OnlineManager), aren't waited for.
+pub async fn settled(client: &QueryClient) {
+    while client.fetching_queries() > 0 {
+        flush().await;
+    }
+}
+
+/// Asserts that the data of a query is the expected one. [`None`](Option::None) expects the query to have no data, or not exist.
+#[track_caller]
+pub fn assert_query_data<K, V>(client: &QueryClient, key: impl Borrow<K>, expected: Option<V>)
+where
+    K: Hash + Eq + Clone + Debug + 'static,
+    V: Clone + PartialEq + Debug + 'static,
+{
+    let key = key.borrow();
+    let data = client.cache::<K, V>().get_data(key);
+    assert_eq!(
+        expected,
+        data,
+        "unexpected data for query ({}, {}) with key {key:?}",
+        std::any::type_name::<K>(),
+        std::any::type_name::<V>(),
+    );
+}
+
+// Yields to the executor once, so other spawned tasks can make progress.
+#[cfg(not(any(feature = "hydrate", feature = "ssr")))]
+async fn yield_now() {
+    let mut yielded = false;
+    std::future::poll_fn(|cx| {
+        if yielded {
+            std::task::Poll::Ready(())
+        } else {
+            yielded = true;
+            cx.waker().wake_by_ref();
+            std::task::Poll::Pending
+        }
+    })
+    .await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{provide_query_client, use_query_client, QueryData, QueryState};
+    use std::{
+        cell::RefCell,
+        future::{poll_fn, Future},
+        pin::pin,
+        rc::Rc,
+        task::Poll,
+    };
+
+    #[test]
+    fn settles_once_fetches_complete() {
+        let _ = create_runtime();
+
+        provide_query_client();
+        let client = use_query_client();
+
+        // A fetch that takes a few turns of the executor.
+        let (query, _) = client.get_or_create_query::<u32, u32>(0);
+        query.set_state(QueryState::Loading);
+        let events = Rc::new(RefCell::new(Vec::new()));
+        let fetch = {
+            let events = events.clone();
+            async move {
+                for _ in 0..3 {
+                    flush().await;
+                }
+                query.set_state(QueryState::Loaded(QueryData::now(1)));
+                events.borrow_mut().push("fetched");
+            }
+        };
+        let settle = {
+            let (client, events) = (client.clone(), events.clone());
+            async move {
+                settled(&client).await;
+                events.borrow_mut().push("settled");
+            }
+        };
+
+        spawn_local(async move {
+            let (mut fetch, mut settle) = (pin!(fetch), pin!(settle));
+            let (mut fetched, mut settled) = (false, false);
+            poll_fn(|cx| {
+                fetched = fetched || fetch.as_mut().poll(cx).is_ready();
+                settled = settled || settle.as_mut().poll(cx).is_ready();
+                if fetched && settled {
+                    Poll::Ready(())
+                } else {
+                    Poll::Pending
+                }
+            })
+            .await
+        });
+
+        assert_eq!(vec!["fetched", "settled"], *RefCell::borrow(&events));
+        assert_query_data::<u32, u32>(&client, 0, Some(1));
+        assert_query_data::<u32, u32>(&client, 1, None);
+    }
+}
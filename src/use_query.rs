@@ -1,12 +1,14 @@
-use crate::query_executor::{create_executor, synchronize_state};
+use crate::query_executor::{create_executor, synchronize_state, RefetchOptions};
 use crate::query_result::QueryResult;
 use crate::{
-    create_query_result, use_query_client, Query, QueryData, QueryOptions, QueryState, RefetchFn,
-    ResourceOption,
+    create_query_result, use_query_client, Instant, Query, QueryData, QueryLogger, QueryOptions,
+    QueryState, RefetchFn, ResourceOption, StringKey,
 };
-use leptos::*;
+use leptos::{leptos_dom::HydrationCtx, *};
+use std::cell::RefCell;
 use std::future::Future;
 use std::hash::Hash;
+use std::rc::Rc;
 use std::time::Duration;
 
 /// Creates a query. Useful for data fetching, caching, and synchronization with server state.
@@ -43,93 +45,262 @@ use std::time::Duration;
 ///     leptos_query::use_query(
 ///         id,
 ///         get_monkey,
-///         QueryOptions {
-///             default_value: None,
-///             refetch_interval: None,
-///             resource_option: ResourceOption::NonBlocking,
-///             stale_time: Some(Duration::from_secs(5)),
-///             cache_time: Some(Duration::from_secs(60)),
-///         },
+///         QueryOptions::builder()
+///             .stale_time(Duration::from_secs(5))
+///             .cache_time(Duration::from_secs(60))
+///             .build(),
 ///     )
 /// }
 ///
 /// ```
 ///
+/// Options can also be reactive, such as a [`Signal<QueryOptions<V>>`](leptos::Signal).
+/// When they change, the query's stale time, cache time, refetch interval, and metadata are updated
+/// without remounting the component. The default value, resource option, and SSR policy are only read once.
+///
+/// ```no_run
+/// use leptos::*;
+/// use leptos_query::*;
+/// use std::time::Duration;
+/// # #[derive(Clone, serde::Deserialize, serde::Serialize)]
+/// # struct Monkey { name: String }
+/// # async fn get_monkey(id: u32) -> Monkey { todo!() }
+///
+/// let (polling, set_polling) = create_signal(true);
+/// let options = Signal::derive(move || {
+///     QueryOptions::builder()
+///         .refetch_interval(polling.get().then_some(Duration::from_secs(10)))
+///         .build()
+/// });
+///
+/// let query = use_query(|| 1, get_monkey, options);
+/// ```
 pub fn use_query<K, V, Fu>(
     key: impl Fn() -> K + 'static,
     fetcher: impl Fn(K) -> Fu + 'static,
-    options: QueryOptions<V>,
+    options: impl Into<MaybeSignal<QueryOptions<V>>>,
 ) -> QueryResult<V, impl RefetchFn>
 where
     K: Hash + Eq + Clone + 'static,
     V: Clone + Serializable + 'static,
     Fu: Future<Output = V> + 'static,
 {
+    let client = use_query_client();
+    let options: MaybeSignal<QueryOptions<V>> = options.into();
+
     // Find relevant state.
-    let query = use_query_client().get_query_signal(key);
+    let query = client.get_query_signal(key);
+
+    let query = Signal::derive(move || query.get().0);
 
-    // Update options.
+    // Register this observer's options with the query, for as long as it observes it.
+    let registered = Rc::new(RefCell::new(None::<(Query<K, V>, u64)>));
+    on_cleanup({
+        let registered = registered.clone();
+        move || {
+            if let Some((query, id)) = registered.take() {
+                query.unregister_options(id);
+            }
+        }
+    });
     create_isomorphic_effect({
         let options = options.clone();
         move |_| {
-            let (query, new) = query.get();
-            if new {
-                query.overwrite_options(options.clone())
-            } else {
-                query.update_options(options.clone())
+            let query = query.get();
+            if let Some((prev_query, id)) = registered.take() {
+                prev_query.unregister_options(id);
             }
+            let id = options.with(|options| query.register_options(options));
+            *registered.borrow_mut() = Some((query, id));
         }
     });
 
-    let query = Signal::derive(move || query.get().0);
+    // Where the query can be fetched is decided once, like the resource option.
+    let fetches_here = options.with_untracked(|options| options.ssr.fetches_here());
 
-    let resource_fetcher = move |query: Query<K, V>| {
+    let logger = client.logger.clone();
+    let resource_fetcher = move |state: RwSignal<QueryState<V>>| {
+        let logger = logger.clone();
         async move {
-            match query.state.get_untracked() {
+            match state.get_untracked() {
                 // Immediately provide cached value.
                 QueryState::Loaded(data)
                 | QueryState::Invalid(data)
-                | QueryState::Fetching(data) => ResourceData(Some(data.data)),
+                | QueryState::Fetching(data) => ResourceData::from(data),
+
+                // Never fetched here, so render without data.
+                QueryState::Created | QueryState::Idle | QueryState::Loading if !fetches_here => {
+                    ResourceData(None, None)
+                }
 
                 // Suspend indefinitely and wait for interruption.
-                QueryState::Created | QueryState::Loading => {
-                    sleep(LONG_TIME).await;
-                    ResourceData(None)
+                QueryState::Created | QueryState::Idle | QueryState::Loading => {
+                    sleep(LONG_TIME, logger.as_ref()).await;
+                    ResourceData(None, None)
                 }
             }
         }
     };
 
-    let resource: Resource<Query<K, V>, ResourceData<V>> = {
-        let default = options.default_value;
-        match options.resource_option {
+    // Seed queries without data from the initial data, before they are read.
+    create_isomorphic_effect({
+        let options = options.clone();
+        move |_| {
+            let query = query.get();
+            if let QueryState::Created = query.state.get_untracked() {
+                let initial_data = options.with_untracked(|options| options.initial_data.clone());
+                if let Some(data) = initial_data.and_then(|initial_data| untrack(|| initial_data()))
+                {
+                    query.set_state(QueryState::Loaded(QueryData::now(data)));
+                }
+            }
+        }
+    });
+
+    // Queries that are never fetched here are idle, rather than about to fetch, until their data arrives some other way.
+    if !fetches_here {
+        create_isomorphic_effect(move |_| {
+            let query = query.get();
+            if let QueryState::Created = query.state.get_untracked() {
+                query.set_state(QueryState::Idle);
+            }
+        });
+    }
+
+    // Keyed by the query's state signal, so the resource type doesn't depend on the key type.
+    // Blocking if any observer of the query is, this one included. See `EffectiveQueryOptions::blocking`.
+    let resource: QueryResource<V> = {
+        #[allow(deprecated)]
+        let default = options.with_untracked(|options| options.default_value.clone());
+        let resource_option = if query.with_untracked(|query| query.effective_options().blocking) {
+            ResourceOption::Blocking
+        } else {
+            ResourceOption::NonBlocking
+        };
+        match resource_option {
             ResourceOption::NonBlocking => create_resource_with_initial_value(
-                move || query.get(),
+                move || query.get().state,
                 resource_fetcher,
-                default.map(|default| ResourceData(Some(default))),
+                default.map(|default| ResourceData(Some(default), None)),
             ),
             ResourceOption::Blocking => {
-                create_blocking_resource(move || query.get(), resource_fetcher)
+                create_blocking_resource(move || query.get().state, resource_fetcher)
             }
         }
     };
 
+    // If the data is equal to the resource's data, when the options compare it. See `QueryOptions::is_data_equal`.
+    let is_data_equal = {
+        let options = options.clone();
+        move |data: &V| {
+            options
+                .with_untracked(|options| options.is_data_equal.clone())
+                .is_some_and(|is_data_equal| {
+                    untrack(|| {
+                        resource.with(|current| {
+                            current
+                                .as_ref()
+                                .and_then(|current| current.0.as_ref())
+                                .is_some_and(|current| is_data_equal(current, data))
+                        })
+                    })
+                })
+        }
+    };
+
     // Ensure always latest value.
-    create_isomorphic_effect(move |_| {
+    // The value is set in place rather than refetching the resource, which would put it back in a loading state.
+    // That way background refetches (stale, invalidated, or interval) never show Suspense fallbacks for data that is already displayed.
+    // While the resource is loading, setting it interrupts Suspense.
+    // Unchanged data only updates the query's state, so subscribers of the data aren't notified.
+    create_isomorphic_effect(move |was_fetching: Option<bool>| {
         let state = query.get().state.get();
-        if let QueryState::Loaded(data) = state {
-            // Interrupt Suspense.
-            if resource.loading().get_untracked() {
-                resource.set(ResourceData(Some(data.data)));
-            } else {
-                resource.refetch();
+        match state {
+            QueryState::Loaded(data) => {
+                if !is_data_equal(&data.data) {
+                    resource.set(ResourceData::from(data));
+                }
+                false
+            }
+            // Partial data, set by the fetcher while it continues. See `FetchContext::set_partial_data`.
+            QueryState::Fetching(data) if was_fetching == Some(true) => {
+                resource.set(ResourceData::from(data));
+                true
             }
+            QueryState::Loading | QueryState::Fetching(_) => true,
+            _ => false,
         }
     });
 
+    let refetch_options = RefetchOptions {
+        quietly: {
+            let options = options.clone();
+            Signal::derive(move || options.with(|options| options.refetch_interval_quietly)).into()
+        },
+        aligned: {
+            let options = options.clone();
+            Signal::derive(move || options.with(|options| options.refetch_interval_aligned)).into()
+        },
+        on_focus: {
+            let options = options.clone();
+            Signal::derive(move || options.with(|options| options.refetch_on_focus)).into()
+        },
+    };
+
     let executor = create_executor(query, fetcher);
+    let executor = move || {
+        if fetches_here {
+            executor()
+        }
+    };
+
+    synchronize_state(query, executor.clone(), refetch_options);
 
-    synchronize_state(query, executor.clone());
+    // Streaming SSR: settle the query state from the server before anything is rendered.
+    // Queries that resolved on the server are loaded immediately, so Transition fallbacks don't flash.
+    // Queries the server is still resolving wait in Loading for the streamed value, instead of fetching again.
+    if HydrationCtx::is_hydrating() {
+        let query = query.get_untracked();
+        if let QueryState::Created | QueryState::Idle = query.state.get_untracked() {
+            match untrack(|| resource.get()).and_then(ResourceData::into_query_data) {
+                Some(data) => {
+                    query.set_state(QueryState::Loaded(data));
+                    check_hydration_mismatch(&query, client.logger.clone());
+                }
+                None if resource.loading().get_untracked() => {
+                    query.set_state(QueryState::Loading);
+                    let executor = executor.clone();
+                    let logger = client.logger.clone();
+                    create_isomorphic_effect(move |settled: Option<bool>| {
+                        if settled == Some(true) || resource.loading().get() {
+                            return settled.unwrap_or(false);
+                        }
+                        if let QueryState::Loading = query.state.get_untracked() {
+                            match untrack(|| resource.get()).and_then(ResourceData::into_query_data)
+                            {
+                                Some(data) => {
+                                    query.set_state(QueryState::Loaded(data));
+                                    check_hydration_mismatch(&query, logger.clone());
+                                }
+                                // Server couldn't resolve the query. Fetch on the client instead.
+                                None => {
+                                    logger.log(&format!(
+                                        "Query ({}, {}) was not resolved on the server. Fetching it on the client instead.",
+                                        std::any::type_name::<K>(),
+                                        std::any::type_name::<V>()
+                                    ));
+                                    query.set_state(QueryState::Created);
+                                    executor()
+                                }
+                            }
+                        }
+                        true
+                    });
+                }
+                _ => (),
+            }
+        }
+    }
 
     // Ensure key changes are considered.
     create_isomorphic_effect({
@@ -138,7 +309,7 @@ where
             let query = query.get();
             if let Some(prev_query) = prev_query {
                 if prev_query != query {
-                    if let QueryState::Created = query.state.get_untracked() {
+                    if let QueryState::Created | QueryState::Idle = query.state.get_untracked() {
                         executor()
                     }
                 }
@@ -150,68 +321,665 @@ where
     let data = Signal::derive({
         let executor = executor.clone();
         move || {
-            let read = resource.get().and_then(|r| r.0);
+            let read = resource.get().and_then(ResourceData::into_query_data);
             let query = query.get_untracked();
 
             // First Read.
             // Putting this in an effect will cause it to always refetch needlessly on the client after SSR.
-            if read.is_none() && matches!(query.state.get_untracked(), QueryState::Created) {
+            if read.is_none()
+                && matches!(
+                    query.state.get_untracked(),
+                    QueryState::Created | QueryState::Idle
+                )
+            {
                 executor()
             // SSR edge case.
             // Given hydrate can happen before resource resolves, signals on the client can be out of sync with resource.
             } else if let Some(ref data) = read {
-                if let QueryState::Created = query.state.get_untracked() {
-                    let updated_at = crate::Instant::now();
-                    let data = QueryData {
-                        data: data.clone(),
-                        updated_at,
-                    };
-                    query.state.set(QueryState::Loaded(data))
+                if let QueryState::Created | QueryState::Idle = query.state.get_untracked() {
+                    query.set_state(QueryState::Loaded(data.clone()))
                 }
             }
-            read
+            read.map(|read| read.data)
+        }
+    });
+
+    let mut result = create_query_result(query, data, executor);
+    result.resource = Some(resource);
+    result
+}
+
+/// Creates a query whose key may not be known yet, such as a key derived from another query's data.
+///
+/// While `key` returns [`None`](Option::None), the query does not exist and nothing is fetched.
+/// The result stays in [`QueryState::Idle`], with no data, and is neither loading nor fetching.
+/// Its [`is_initial_loading`](QueryResult::is_initial_loading) flag is false until the key resolves.
+/// Once `key` returns a value, the query behaves exactly like [`use_query`].
+/// If the key becomes unresolved again afterwards, the last resolved key is kept.
+///
+/// For server side rendering, prefer keys that resolve during the first render,
+/// so the query's resource is created in the same order on the server and the client.
+///
+/// Example
+/// ```no_run
+/// use leptos::*;
+/// use leptos_query::*;
+/// # #[derive(Clone, serde::Deserialize, serde::Serialize)]
+/// # struct User { id: u32, zoo_id: u32 }
+/// # #[derive(Clone, serde::Deserialize, serde::Serialize)]
+/// # struct Zoo { name: String }
+/// # async fn get_user(id: u32) -> User { todo!() }
+/// # async fn get_zoo(id: u32) -> Zoo { todo!() }
+///
+/// let user = use_query(|| 1, get_user, QueryOptions::default());
+///
+/// // Only fetched once the user is loaded.
+/// let zoo = use_dependent_query(
+///     move || user.data.get().map(|user| user.zoo_id),
+///     get_zoo,
+///     QueryOptions::default(),
+/// );
+/// ```
+pub fn use_dependent_query<K, V, Fu>(
+    key: impl Fn() -> Option<K> + 'static,
+    fetcher: impl Fn(K) -> Fu + 'static,
+    options: impl Into<MaybeSignal<QueryOptions<V>>>,
+) -> QueryResult<V, impl RefetchFn>
+where
+    K: Hash + Eq + Clone + 'static,
+    V: Clone + Serializable + 'static,
+    Fu: Future<Output = V> + 'static,
+{
+    let options: MaybeSignal<QueryOptions<V>> = options.into();
+    let owner = Owner::current().expect("Owner to be present");
+    let key = create_memo(move |_| key());
+    let resolved = create_rw_signal(None);
+
+    let create = {
+        let fetcher = Rc::new(fetcher);
+        move |initial: K| {
+            let fetcher = fetcher.clone();
+            let options = options.clone();
+            with_owner(owner, move || {
+                let last_key = Rc::new(RefCell::new(initial));
+                use_query(
+                    move || {
+                        let mut last_key = last_key.borrow_mut();
+                        if let Some(key) = key.get() {
+                            *last_key = key;
+                        }
+                        last_key.clone()
+                    },
+                    move |key| fetcher(key),
+                    options,
+                )
+            })
+        }
+    };
+
+    // Create the query as soon as the key is known, preferably during the first render.
+    match key.get_untracked() {
+        Some(initial) => resolved.set(Some(create(initial))),
+        None => {
+            create_isomorphic_effect(move |created: Option<bool>| {
+                if created == Some(true) {
+                    return true;
+                }
+                match key.get() {
+                    Some(initial) => {
+                        resolved.set(Some(untrack(|| create(initial))));
+                        true
+                    }
+                    None => false,
+                }
+            });
+        }
+    }
+
+    let data = Signal::derive(move || resolved.with(|r| r.as_ref().and_then(|r| r.data.get())));
+    let state = Signal::derive(move || {
+        resolved.with(|r| {
+            r.as_ref()
+                .map(|r| r.state.get())
+                .unwrap_or(QueryState::Idle)
+        })
+    });
+    let flag = move |field: fn(&QueryResult<V, _>) -> Signal<bool>| {
+        Signal::derive(move || resolved.with(|r| r.as_ref().is_some_and(|r| field(r).get())))
+    };
+
+    QueryResult {
+        data,
+        state,
+        is_loading: flag(|r| r.is_loading),
+        is_initial_loading: flag(|r| r.is_initial_loading),
+        is_fetching: flag(|r| r.is_fetching),
+        is_stale: flag(|r| r.is_stale),
+        is_invalid: flag(|r| r.is_invalid),
+        is_paused: flag(|r| r.is_paused),
+        progress: Signal::derive(move || {
+            resolved.with(|r| r.as_ref().and_then(|r| r.progress.get()))
+        }),
+        resource: None,
+        handle: Some(Rc::new(resolved)),
+        updated_at: Signal::derive(move || {
+            resolved.with(|r| r.as_ref().and_then(|r| r.updated_at.get()))
+        }),
+        refetch: move || {
+            if let Some(refetch) =
+                resolved.with_untracked(|r| r.as_ref().map(|r| r.refetch.clone()))
+            {
+                refetch()
+            }
+        },
+    }
+}
+
+/// Creates a query whose key can't implement `Hash + Eq`, such as a key with floats or third-party types.
+///
+/// The query is cached by the string `key_string` derives from the key, and the fetcher receives the key itself.
+/// Keys with the same string share a cache entry, and the fetcher receives the key that created it.
+/// Otherwise, the query behaves exactly like [`use_query`]. Its cache key type is [`StringKey<K>`].
+///
+/// Example
+/// ```no_run
+/// use leptos::*;
+/// use leptos_query::*;
+/// # #[derive(Clone, serde::Deserialize, serde::Serialize)]
+/// # struct Place { name: String }
+/// # async fn get_nearest_place(position: (f64, f64)) -> Place { todo!() }
+///
+/// let position = create_rw_signal((51.5074, -0.1278));
+/// let place = use_query_keyed_by_string(
+///     |(lat, lon)| format!("{lat:.4},{lon:.4}"),
+///     move || position.get(),
+///     get_nearest_place,
+///     QueryOptions::default(),
+/// );
+/// ```
+pub fn use_query_keyed_by_string<K, V, Fu>(
+    key_string: impl Fn(&K) -> String + 'static,
+    key: impl Fn() -> K + 'static,
+    fetcher: impl Fn(K) -> Fu + 'static,
+    options: impl Into<MaybeSignal<QueryOptions<V>>>,
+) -> QueryResult<V, impl RefetchFn>
+where
+    K: Clone + 'static,
+    V: Clone + Serializable + 'static,
+    Fu: Future<Output = V> + 'static,
+{
+    use_query(
+        move || StringKey::new(key(), &key_string),
+        move |key: StringKey<K>| fetcher(key.into_key()),
+        options,
+    )
+}
+
+// Debug builds only: warns if the first value the client fetches for a hydrated query differs from the server's.
+// Catches nondeterministic fetchers, which cause hydration mismatches.
+fn check_hydration_mismatch<K, V>(query: &Query<K, V>, logger: Rc<dyn QueryLogger>)
+where
+    K: 'static,
+    V: Serializable + 'static,
+{
+    if !cfg!(debug_assertions) {
+        return;
+    }
+    let Some((hydrated_at, Ok(server))) = query
+        .state
+        .with_untracked(|state| state.query_data().map(|d| (d.updated_at, d.data.ser())))
+    else {
+        return;
+    };
+
+    let state = query.state;
+    create_effect(move |checked: Option<bool>| {
+        if checked == Some(true) {
+            return true;
         }
+        state.with(|state| match state {
+            QueryState::Loaded(data) if data.updated_at != hydrated_at => {
+                if let Ok(client) = data.data.ser() {
+                    if client != server {
+                        logger.warn(&format!(
+                            "Hydration mismatch for query ({}, {}): the client fetched different data than the server rendered. {}",
+                            std::any::type_name::<K>(),
+                            std::any::type_name::<V>(),
+                            describe_difference(&server, &client)
+                        ));
+                    }
+                }
+                true
+            }
+            _ => false,
+        })
     });
+}
 
-    create_query_result(query, data, executor)
+// Shows where two serialized values first differ.
+fn describe_difference(server: &str, client: &str) -> String {
+    const CONTEXT: usize = 40;
+    let index = server
+        .chars()
+        .zip(client.chars())
+        .take_while(|(a, b)| a == b)
+        .count();
+    let excerpt = |value: &str| -> String {
+        value
+            .chars()
+            .skip(index.saturating_sub(CONTEXT))
+            .take(2 * CONTEXT)
+            .collect()
+    };
+    format!(
+        "First difference at character {index}. Server: `{}` Client: `{}`",
+        excerpt(server),
+        excerpt(client)
+    )
 }
 
 const LONG_TIME: Duration = Duration::from_secs(60 * 60 * 24);
 
-async fn sleep(duration: Duration) {
+async fn sleep(duration: Duration, logger: &dyn QueryLogger) {
     use cfg_if::cfg_if;
     cfg_if! {
         if #[cfg(feature = "hydrate")] {
+            let _ = logger;
             gloo_timers::future::sleep(duration).await;
         } else if #[cfg(feature = "ssr")] {
+            let _ = logger;
             tokio::time::sleep(duration).await;
         } else {
             let _ = duration;
-            logging::debug_warn!("You are missing a Cargo feature for leptos_query. Please use one of 'ssr' or 'hydrate'")
+            if cfg!(debug_assertions) {
+                logger.warn("You are missing a Cargo feature for leptos_query. Please use one of 'ssr' or 'hydrate'")
+            }
         }
     }
 }
 
-/// Wrapper type to enable using `Serializable`
+/// The [`Resource`] backing a query created with [`use_query`].
+///
+/// See [`QueryResult::resource`].
+pub type QueryResource<V> = Resource<RwSignal<QueryState<V>>, ResourceData<V>>;
+
+/// The value of a [`QueryResource`]. Holds [`None`](Option::None) until the query has data,
+/// along with when the data was fetched.
+///
+/// The fetch time is sent from the server along with the data,
+/// so hydrated queries are only stale once their `stale_time` has elapsed since the server fetched them.
+///
+/// Wrapper type to enable using `Serializable`.
 #[derive(Clone, Debug)]
-struct ResourceData<V>(Option<V>);
+pub struct ResourceData<V>(pub(crate) Option<V>, pub(crate) Option<Instant>);
+
+impl<V> ResourceData<V> {
+    /// The data, if the query has any.
+    pub fn data(&self) -> Option<&V> {
+        self.0.as_ref()
+    }
+
+    /// Consumes this, returning the data, if the query has any.
+    pub fn into_data(self) -> Option<V> {
+        self.0
+    }
+
+    // Data without a fetch time, or with one from a server clock that is ahead of the client, is considered fetched now.
+    fn into_query_data(self) -> Option<QueryData<V>> {
+        let now = Instant::now();
+        let updated_at = self.1.map_or(now, |updated_at| updated_at.min(now));
+        self.0.map(|data| QueryData { data, updated_at })
+    }
+}
 
+impl<V> From<QueryData<V>> for ResourceData<V> {
+    fn from(data: QueryData<V>) -> Self {
+        ResourceData(Some(data.data), Some(data.updated_at))
+    }
+}
+
+// Serialized as `<updated_at millis>|<data>`, with an empty fetch time if there is none, or as `null` without data.
+// The fetch time never contains `|`, so the data is everything after the first one, whatever it contains.
 impl<V> Serializable for ResourceData<V>
 where
     V: Serializable,
 {
     fn ser(&self) -> Result<String, SerializationError> {
-        if let Some(ref value) = self.0 {
-            value.ser()
-        } else {
-            Ok("null".to_string())
+        match &self.0 {
+            Some(value) => {
+                let updated_at = self
+                    .1
+                    .map(|updated_at| updated_at.as_millis().to_string())
+                    .unwrap_or_default();
+                Ok(format!("{updated_at}|{}", value.ser()?))
+            }
+            None => Ok("null".to_string()),
         }
     }
 
     fn de(bytes: &str) -> Result<Self, SerializationError> {
-        match bytes {
-            "" | "null" => Ok(ResourceData(None)),
-            v => <V>::de(v).map(Some).map(ResourceData),
+        let Some((updated_at, data)) = bytes.split_once('|') else {
+            return match bytes {
+                "" | "null" => Ok(ResourceData(None, None)),
+                _ => Err(SerializationError::Deserialize(Rc::new(
+                    InvalidResourceData,
+                ))),
+            };
+        };
+        let updated_at = match updated_at {
+            "" => None,
+            millis => {
+                Some(Instant::from_millis(millis.parse().map_err(|error| {
+                    SerializationError::Deserialize(Rc::new(error))
+                })?))
+            }
+        };
+        <V>::de(data).map(|data| ResourceData(Some(data), updated_at))
+    }
+}
+
+#[derive(Debug)]
+struct InvalidResourceData;
+
+impl std::fmt::Display for InvalidResourceData {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "malformed query resource data")
+    }
+}
+
+impl std::error::Error for InvalidResourceData {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::SsrPolicy;
+    use std::cell::Cell;
+
+    #[test]
+    fn resource_data_keeps_server_fetch_time() {
+        let fetched = ResourceData(Some(1_u32), Some(Instant::from_millis(1_000)));
+        let bytes = fetched.ser().unwrap();
+        assert_eq!("1000|1", bytes);
+
+        let hydrated = ResourceData::<u32>::de(&bytes).unwrap().into_query_data();
+        assert_eq!(
+            Some(QueryData {
+                data: 1,
+                updated_at: Instant::from_millis(1_000)
+            }),
+            hydrated
+        );
+
+        // Fetch times from a server clock that is ahead are capped to now.
+        let ahead = ResourceData(
+            Some(1_u32),
+            Some(Instant(Instant::now().0 + Duration::from_secs(60))),
+        );
+        let hydrated = ahead.into_query_data().unwrap();
+        assert!(hydrated.updated_at <= Instant::now());
+
+        assert!(ResourceData::<u32>::de("null").unwrap().0.is_none());
+        assert!(ResourceData::<u32>::de("1").is_err());
+    }
+
+    // Serializes as is, so the payload can contain anything.
+    #[derive(Clone, Debug, PartialEq)]
+    struct Raw(String);
+
+    impl Serializable for Raw {
+        fn ser(&self) -> Result<String, SerializationError> {
+            Ok(self.0.clone())
+        }
+
+        fn de(bytes: &str) -> Result<Self, SerializationError> {
+            Ok(Raw(bytes.to_string()))
         }
     }
+
+    #[test]
+    fn resource_data_round_trips_any_payload() {
+        let updated_at = Some(Instant::from_millis(1_000));
+        for payload in ["12|34", "|", "", "null", "a|b|c"] {
+            for updated_at in [updated_at, None] {
+                let data = ResourceData(Some(Raw(payload.to_string())), updated_at);
+                let round_trip = ResourceData::<Raw>::de(&data.ser().unwrap()).unwrap();
+                assert_eq!(
+                    (Some(Raw(payload.to_string())), updated_at),
+                    (round_trip.0, round_trip.1)
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn skips_unchanged_data() {
+        let _ = create_runtime();
+
+        crate::provide_query_client();
+
+        let value = Rc::new(Cell::new(1_u32));
+        let query = use_query(
+            || 0_u32,
+            {
+                let value = value.clone();
+                move |_| {
+                    let value = value.get();
+                    async move { value }
+                }
+            },
+            QueryOptions::builder().skip_unchanged_data().build(),
+        );
+
+        let notified = Rc::new(Cell::new(0));
+        create_isomorphic_effect({
+            let notified = notified.clone();
+            move |_| {
+                query.data.track();
+                notified.set(notified.get() + 1);
+            }
+        });
+        assert_eq!(Some(1), query.data.get_untracked());
+        let notifications = notified.get();
+
+        // Refetched, but unchanged.
+        (query.refetch)();
+        assert_eq!(notifications, notified.get());
+
+        value.set(2);
+        (query.refetch)();
+        assert_eq!(Some(2), query.data.get_untracked());
+        assert_eq!(notifications + 1, notified.get());
+    }
+
+    #[test]
+    fn compares_data_with_the_given_function() {
+        let _ = create_runtime();
+
+        crate::provide_query_client();
+
+        let fetches = Rc::new(Cell::new(0_u32));
+        let version = Rc::new(Cell::new(1_u32));
+        let query = use_query(
+            || 0_u32,
+            {
+                let fetches = fetches.clone();
+                let version = version.clone();
+                move |_| {
+                    fetches.set(fetches.get() + 1);
+                    // Only the version matters, not the fetch count.
+                    let page = (version.get(), fetches.get());
+                    async move { page }
+                }
+            },
+            QueryOptions::builder()
+                .is_data_equal(|page: &(u32, u32), other: &(u32, u32)| page.0 == other.0)
+                .build(),
+        );
+
+        let notified = Rc::new(Cell::new(0));
+        create_isomorphic_effect({
+            let notified = notified.clone();
+            move |_| {
+                query.data.track();
+                notified.set(notified.get() + 1);
+            }
+        });
+        let notifications = notified.get();
+
+        // Refetched with a new fetch count, but the same version.
+        (query.refetch)();
+        assert_eq!(Some((1, 1)), query.data.get_untracked());
+        assert_eq!(notifications, notified.get());
+
+        version.set(2);
+        (query.refetch)();
+        assert_eq!(Some((2, 3)), query.data.get_untracked());
+        assert_eq!(notifications + 1, notified.get());
+    }
+
+    #[test]
+    fn queries_not_fetched_here_are_idle() {
+        let _ = create_runtime();
+
+        crate::provide_query_client();
+        let client = crate::use_query_client();
+
+        let fetches = Rc::new(Cell::new(0));
+        let mount = |ssr: SsrPolicy| {
+            let fetches = fetches.clone();
+            let query = use_query(
+                || 0_u32,
+                move |_| {
+                    fetches.set(fetches.get() + 1);
+                    async { 1_u32 }
+                },
+                QueryOptions::builder().ssr(ssr).build(),
+            );
+            create_isomorphic_effect(move |_| query.data.track());
+            query
+        };
+
+        // Not fetched where the code runs, so it's idle rather than about to load.
+        let not_here = if cfg!(feature = "ssr") {
+            SsrPolicy::ClientOnly
+        } else {
+            SsrPolicy::ServerOnly
+        };
+        let elsewhere = mount(not_here);
+        assert!(matches!(elsewhere.state.get_untracked(), QueryState::Idle));
+        assert!(!elsewhere.is_initial_loading.get_untracked());
+        assert!(!client.refetch_query_with::<u32, u32, _>(0, |_| async { 2 }));
+        assert_eq!(0, fetches.get());
+
+        // Until an observer that fetches here mounts.
+        let everywhere = mount(SsrPolicy::ServerAndClient);
+        assert_eq!(1, fetches.get());
+        assert_eq!(Some(1), everywhere.data.get_untracked());
+        assert!(matches!(
+            elsewhere.state.get_untracked(),
+            QueryState::Loaded(_)
+        ));
+    }
+
+    #[test]
+    fn dependent_query_is_idle_until_its_key_resolves() {
+        let _ = create_runtime();
+
+        crate::provide_query_client();
+        let client = crate::use_query_client();
+
+        let zoo_id = create_rw_signal(None::<u32>);
+        let zoo = use_dependent_query(
+            move || zoo_id.get(),
+            |id| async move { id * 10 },
+            QueryOptions::default(),
+        );
+        create_isomorphic_effect(move |_| zoo.data.track());
+
+        assert!(matches!(zoo.state.get_untracked(), QueryState::Idle));
+        assert_eq!(None, zoo.data.get_untracked());
+        assert!(!zoo.is_loading.get_untracked());
+        assert!(!zoo.is_initial_loading.get_untracked());
+        assert_eq!(0, client.cache::<u32, u32>().len());
+
+        zoo_id.set(Some(1));
+        assert_eq!(Some(10), zoo.data.get_untracked());
+        assert!(matches!(zoo.state.get_untracked(), QueryState::Loaded(_)));
+
+        // Keeps the last resolved key.
+        zoo_id.set(None);
+        assert_eq!(Some(10), zoo.data.get_untracked());
+        assert_eq!(1, client.cache::<u32, u32>().len());
+    }
+
+    #[test]
+    fn reactive_options_apply_to_the_query() {
+        let _ = create_runtime();
+
+        crate::provide_query_client();
+        let client = crate::use_query_client();
+
+        let options = create_rw_signal(QueryOptions::<u32> {
+            stale_time: Some(Duration::from_secs(10)),
+            refetch_interval: None,
+            ..QueryOptions::default()
+        });
+        let query = use_query(|| 0_u32, |_| async { 1_u32 }, options);
+        create_isomorphic_effect(move |_| query.data.track());
+
+        let effective = || client.get_query_options::<u32, u32>(0).unwrap();
+        assert_eq!(Some(Duration::from_secs(10)), effective().stale_time);
+        assert_eq!(None, effective().refetch_interval);
+
+        options.update(|options| {
+            options.stale_time = Some(Duration::from_secs(30));
+            options.refetch_interval = Some(Duration::from_secs(60));
+        });
+        assert_eq!(Some(Duration::from_secs(30)), effective().stale_time);
+        assert_eq!(Some(Duration::from_secs(60)), effective().refetch_interval);
+        // Replaces this observer's options, rather than adding to them.
+        assert_eq!(1, effective().observers);
+    }
+
+    #[test]
+    fn initial_data_seeds_queries_without_data() {
+        let _ = create_runtime();
+
+        crate::provide_query_client();
+        let client = crate::use_query_client();
+
+        let fetches = Rc::new(Cell::new(0));
+        let mount = |id: u32, initial: Option<u32>| {
+            let fetches = fetches.clone();
+            let query = use_query(
+                move || id,
+                move |id| {
+                    fetches.set(fetches.get() + 1);
+                    async move { id * 10 }
+                },
+                QueryOptions {
+                    stale_time: Some(Duration::from_secs(60)),
+                    initial_data: Some(Rc::new(move || initial)),
+                    ..QueryOptions::default()
+                },
+            );
+            create_isomorphic_effect(move |_| query.data.track());
+            query
+        };
+
+        // Fresh, so it's not fetched.
+        let seeded = mount(0, Some(5));
+        assert_eq!(Some(5), seeded.data.get_untracked());
+        assert_eq!(Some(5), client.cache::<u32, u32>().get_data(0));
+        assert_eq!(0, fetches.get());
+
+        // Without initial data, it's fetched as usual.
+        let fetched = mount(1, None);
+        assert_eq!(Some(10), fetched.data.get_untracked());
+        assert_eq!(1, fetches.get());
+
+        // Queries that have data already aren't seeded.
+        let cached = mount(1, Some(5));
+        assert_eq!(Some(10), cached.data.get_untracked());
+    }
 }
@@ -1,12 +1,19 @@
-use std::{cell::Cell, rc::Rc, time::Duration};
+use std::{cell::Cell, collections::HashMap, rc::Rc, time::Duration};
 
-use leptos::{leptos_dom::helpers::TimeoutHandle, *};
+use leptos::*;
 
-use crate::instant::Instant;
+use crate::{instant::Instant, scheduler::TimerHandle, INFINITE_TIME};
 
-pub(crate) fn use_timeout(func: impl Fn() -> Option<TimeoutHandle> + 'static) -> impl Fn() {
+// Map keyed by query keys.
+// With the `rustc-hash` feature, keys are hashed with FxHash, which is faster than the default SipHash for typical keys.
+#[cfg(feature = "rustc-hash")]
+pub(crate) type KeyMap<K, V> = HashMap<K, V, std::hash::BuildHasherDefault<rustc_hash::FxHasher>>;
+#[cfg(not(feature = "rustc-hash"))]
+pub(crate) type KeyMap<K, V> = HashMap<K, V>;
+
+pub(crate) fn use_timeout(func: impl Fn() -> Option<TimerHandle> + 'static) -> impl Fn() {
     // Saves last interval to be cleared on cleanup.
-    let timeout: Rc<Cell<Option<TimeoutHandle>>> = Rc::new(Cell::new(None));
+    let timeout: Rc<Cell<Option<TimerHandle>>> = Rc::new(Cell::new(None));
     let clean_up = {
         let interval = timeout.clone();
         move || {
@@ -18,14 +25,14 @@ pub(crate) fn use_timeout(func: impl Fn() -> Option<TimeoutHandle> + 'static) ->
 
     on_cleanup(clean_up.clone());
 
-    create_effect(move |maybe_handle: Option<Option<TimeoutHandle>>| {
+    create_effect(move |maybe_handle: Option<Option<TimerHandle>>| {
         let maybe_handle = maybe_handle.flatten().or_else(|| timeout.take());
         if let Some(handle) = maybe_handle {
             handle.clear();
         }
 
         let result = func();
-        timeout.set(result);
+        timeout.set(result.clone());
 
         result
     });
@@ -34,12 +41,23 @@ pub(crate) fn use_timeout(func: impl Fn() -> Option<TimeoutHandle> + 'static) ->
 }
 
 pub(crate) fn time_until_stale(updated_at: Instant, stale_time: Duration) -> Duration {
-    let updated_at = updated_at.0.as_millis() as i64;
-    let now = Instant::now().0.as_millis() as i64;
-    let stale_time = stale_time.as_millis() as i64;
-    let result = (updated_at + stale_time) - now;
-    let ensure_non_negative = result.max(0);
-    Duration::from_millis(ensure_non_negative as u64)
+    // Saturates, so INFINITE_TIME never elapses.
+    updated_at
+        .0
+        .saturating_add(stale_time)
+        .saturating_sub(Instant::now().0)
+}
+
+// The first multiple of the interval since the Unix epoch that is after the instant.
+pub(crate) fn next_aligned(after: Instant, interval: Duration) -> Instant {
+    let interval = interval.as_millis().max(1);
+    let after = after.0.as_millis();
+    let next = (after / interval + 1) * interval;
+    Instant(Duration::from_millis(next as u64))
+}
+
+pub(crate) fn time_until(instant: Instant) -> Duration {
+    instant.0.saturating_sub(Instant::now().0)
 }
 
 pub(crate) fn maybe_time_until_stale(
@@ -47,7 +65,28 @@ pub(crate) fn maybe_time_until_stale(
     stale_time: Option<Duration>,
 ) -> Option<Duration> {
     match (updated_at, stale_time) {
+        // No timer is needed for a time that never elapses.
+        (_, Some(INFINITE_TIME)) => None,
         (Some(last_updated), Some(stale_time)) => Some(time_until_stale(last_updated, stale_time)),
         _ => None,
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn aligns_to_the_next_multiple_of_the_interval() {
+        let minute = Duration::from_secs(60);
+        let at = |secs| Instant(Duration::from_secs(secs));
+
+        assert_eq!(at(120), next_aligned(at(61), minute));
+        // Data updated right at a boundary is refetched at the next one.
+        assert_eq!(at(180), next_aligned(at(120), minute));
+        assert_eq!(
+            Instant(Duration::from_millis(120_000)),
+            next_aligned(Instant(Duration::from_millis(119_999)), minute)
+        );
+    }
+}